@@ -0,0 +1,66 @@
+use adw::prelude::*;
+use adw::subclass::prelude::ObjectSubclassIsExt;
+use gtk::{
+    gio::{self, ActionEntry, ListStore},
+    glib::{self, Object},
+};
+
+use crate::{APP_ID, history::Command, main_window::MainWindow};
+
+glib::wrapper! {
+    pub struct Application(ObjectSubclass<application_imp::ApplicationImp>)
+        @extends adw::Application, gtk::Application, gio::Application,
+        @implements gio::ActionGroup, gio::ActionMap;
+}
+
+mod application_imp;
+
+impl Application {
+    pub fn new() -> Self {
+        Object::builder().property("application-id", APP_ID).build()
+    }
+
+    /// Collections shared by all windows
+    pub fn collections(&self) -> ListStore {
+        self.imp().collections.clone()
+    }
+
+    /// Apply `command` and record it for undoing
+    pub fn execute(&self, command: Command) {
+        self.imp().execute(command);
+    }
+    pub fn undo(&self) {
+        self.imp().undo();
+    }
+    pub fn redo(&self) {
+        self.imp().redo();
+    }
+
+    /// Save the collections right away
+    pub fn save(&self) {
+        self.imp().save();
+    }
+
+    /// Message about collections which could not be read, to be shown by the first window
+    ///
+    /// Returns the body of the message and whether there are backups to restore.
+    pub fn take_load_error(&self) -> Option<(String, bool)> {
+        self.imp().load_error.take()
+    }
+
+    fn setup_actions(&self) {
+        self.add_action_entries([ActionEntry::builder("new-window")
+            .activate(|app: &Self, _, _| {
+                app.new_window().present();
+            })
+            .build()]);
+
+        self.set_accels_for_action("app.new-window", &["<Ctrl>n"]);
+        self.set_accels_for_action("win.undo", &["<Ctrl>z"]);
+        self.set_accels_for_action("win.redo", &["<Ctrl><Shift>z"]);
+    }
+
+    fn new_window(&self) -> MainWindow {
+        MainWindow::builder().application(self).build()
+    }
+}
@@ -0,0 +1,262 @@
+use std::{
+    cell::{Cell, OnceCell, RefCell},
+    time::Duration,
+};
+
+use adw::{prelude::*, subclass::prelude::*};
+use gtk::{
+    gio::{self, FileMonitor, FileMonitorEvent, FileMonitorFlags, ListStore},
+    glib::{self, Properties, SourceId},
+};
+
+use crate::{
+    autosave::Autosave,
+    collection_object::CollectionObject,
+    history::{Command, History},
+    main_window::MainWindow,
+    storage,
+};
+
+/// Time without further changes to the data file after which it is reloaded
+const RELOAD_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Properties)]
+#[properties(wrapper_type = super::Application)]
+pub struct ApplicationImp {
+    /// ListStore storing CollectionObject, shown by every window
+    pub(super) collections: ListStore,
+    history: History,
+    #[property(get)]
+    can_undo: Cell<bool>,
+    #[property(get)]
+    can_redo: Cell<bool>,
+    autosave: OnceCell<Autosave>,
+    /// Reports changes made to the data file by others, e.g. another instance or a sync tool
+    file_monitor: OnceCell<FileMonitor>,
+    reload_timeout: RefCell<Option<SourceId>>,
+    /// Body of the message about unreadable collections and whether there are backups
+    pub(super) load_error: RefCell<Option<(String, bool)>>,
+}
+
+impl ApplicationImp {
+    pub(super) fn execute(&self, command: Command) {
+        command.apply();
+        self.history.push(command);
+        self.update_history_state();
+    }
+    pub(super) fn undo(&self) {
+        if self.history.undo() {
+            self.update_history_state();
+        }
+    }
+    pub(super) fn redo(&self) {
+        if self.history.redo() {
+            self.update_history_state();
+        }
+    }
+    fn update_history_state(&self) {
+        if self.can_undo.replace(self.history.can_undo()) != self.history.can_undo() {
+            self.obj().notify_can_undo();
+        }
+        if self.can_redo.replace(self.history.can_redo()) != self.history.can_redo() {
+            self.obj().notify_can_redo();
+        }
+    }
+
+    /// Save state to filesystem
+    pub(super) fn save(&self) {
+        self.autosave.get().unwrap().save_now();
+    }
+    /// Load the collections, falling back to the newest readable backup when the data
+    /// file can't be read
+    fn load(&self) {
+        let err = match storage::load() {
+            Ok(collections) => {
+                for c in collections {
+                    self.collections.append(&c);
+                }
+                return;
+            }
+            Err(err) => err,
+        };
+        eprintln!("Error occurred trying to load collections: {err}");
+
+        let backups = storage::backups();
+        let mut body = match storage::quarantine() {
+            Ok(path) => format!(
+                "The saved collections could not be read, the file was moved to {}.",
+                path.display()
+            ),
+            Err(err) => {
+                eprintln!("Error occurred trying to move the data file aside: {err}");
+                // Saving anything would overwrite the data, which may still be recovered
+                storage::refuse_saving();
+                let body = format!(
+                    "The saved collections could not be read, nor could {} be moved aside. \
+                     Changes are not saved until a backup is restored, so that the file is \
+                     not overwritten.",
+                    storage::path().display()
+                );
+                self.load_error.replace(Some((body, !backups.is_empty())));
+                return;
+            }
+        };
+        match backups
+            .iter()
+            .find_map(|backup| Some((backup, storage::load_from(backup.path()).ok()?)))
+        {
+            Some((backup, collections)) => {
+                for c in collections {
+                    self.collections.append(&c);
+                }
+                body.push_str(&format!(
+                    " The backup from {} was loaded instead.",
+                    backup.label()
+                ));
+            }
+            None => body.push_str(" No readable backup was found."),
+        }
+
+        self.load_error.replace(Some((body, !backups.is_empty())));
+    }
+    /// Reload the collections whenever the data file changes
+    fn watch_data_file(&self) {
+        let file = gio::File::for_path(storage::path());
+        let monitor = match file.monitor_file(FileMonitorFlags::WATCH_MOVES, gio::Cancellable::NONE)
+        {
+            Ok(monitor) => monitor,
+            Err(err) => {
+                eprintln!("Error occurred trying to watch the data file: {err}");
+                return;
+            }
+        };
+
+        let app = self.downgrade();
+        monitor.connect_changed(move |_, _, _, event| {
+            if let Some(app) = app.upgrade()
+                && !matches!(
+                    event,
+                    FileMonitorEvent::Deleted
+                        | FileMonitorEvent::MovedOut
+                        | FileMonitorEvent::AttributeChanged
+                        | FileMonitorEvent::PreUnmount
+                        | FileMonitorEvent::Unmounted
+                )
+            {
+                app.schedule_reload();
+            }
+        });
+        self.file_monitor.set(monitor).unwrap();
+    }
+    /// Reload after `RELOAD_DELAY`, as writing a file emits several events
+    fn schedule_reload(&self) {
+        if let Some(timeout) = self.reload_timeout.take() {
+            timeout.remove();
+        }
+
+        let app = self.downgrade();
+        self.reload_timeout
+            .replace(Some(glib::timeout_add_local_once(
+                RELOAD_DELAY,
+                move || {
+                    if let Some(app) = app.upgrade() {
+                        app.reload_timeout.take();
+                        app.reload();
+                    }
+                },
+            )));
+    }
+    /// Merge changes made to the data file by others into the collections
+    ///
+    /// The data file is read on a background thread.
+    fn reload(&self) {
+        let app = self.downgrade();
+        glib::spawn_future_local(async move {
+            let changes = gio::spawn_blocking(storage::read_changes).await;
+            let Some(app) = app.upgrade() else {
+                return;
+            };
+            let changed = match changes {
+                Ok(Ok(Some(changes))) => storage::apply_changes(&app.collections, changes),
+                Ok(Ok(None)) => Ok(false),
+                Ok(Err(err)) => Err(err),
+                Err(_) => Err("reader panicked".into()),
+            };
+            match changed {
+                Ok(true) => {
+                    // Positions recorded by the commands may no longer match
+                    app.history.clear();
+                    app.update_history_state();
+                    for window in app.obj().windows() {
+                        if let Ok(window) = window.downcast::<MainWindow>() {
+                            WidgetExt::activate_action(
+                                &window,
+                                "win.toast",
+                                Some(&"Collections changed on disk and were reloaded".to_variant()),
+                            )
+                            .unwrap();
+                        }
+                    }
+                }
+                Ok(false) => {}
+                Err(err) => eprintln!("Error occurred trying to reload collections: {err}"),
+            }
+        });
+    }
+}
+
+impl Default for ApplicationImp {
+    fn default() -> Self {
+        Self {
+            collections: ListStore::new::<CollectionObject>(),
+            history: Default::default(),
+            can_undo: Default::default(),
+            can_redo: Default::default(),
+            autosave: Default::default(),
+            file_monitor: Default::default(),
+            reload_timeout: Default::default(),
+            load_error: Default::default(),
+        }
+    }
+}
+
+#[glib::object_subclass]
+impl ObjectSubclass for ApplicationImp {
+    const NAME: &'static str = "GtkRsTestApplication";
+    type Type = super::Application;
+    type ParentType = adw::Application;
+}
+
+#[glib::derived_properties]
+impl ObjectImpl for ApplicationImp {}
+impl ApplicationImpl for ApplicationImp {
+    fn startup(&self) {
+        self.parent_startup();
+
+        self.obj().setup_actions();
+
+        // Restore state from filesystem
+        self.load();
+        self.autosave
+            .get_or_init(|| Autosave::new(&self.collections));
+        self.watch_data_file();
+    }
+
+    fn activate(&self) {
+        self.parent_activate();
+
+        match self.obj().active_window() {
+            Some(window) => window.present(),
+            None => self.obj().new_window().present(),
+        }
+    }
+
+    fn shutdown(&self) {
+        // Store state to filesystem before exiting
+        self.save();
+
+        self.parent_shutdown();
+    }
+}
+impl GtkApplicationImpl for ApplicationImp {}
+impl AdwApplicationImpl for ApplicationImp {}
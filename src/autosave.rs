@@ -0,0 +1,184 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashSet,
+    rc::Rc,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use gtk::{
+    gio::{self, ListStore, prelude::*},
+    glib::{self, SourceId},
+};
+
+use crate::{collection_object::CollectionObject, storage};
+
+/// Time without further changes after which the collections are saved
+const DELAY: Duration = Duration::from_secs(2);
+
+/// Saves the collections in the background shortly after they stop changing
+///
+/// The collections are serialized on the main thread, as the objects can't leave it,
+/// and written to disk on a worker thread. Changes made while a write is running are
+/// saved once it finished.
+#[derive(Clone)]
+pub struct Autosave(Rc<Inner>);
+
+struct Inner {
+    collections: ListStore,
+    /// Collections whose changes schedule a save
+    watched_collections: RefCell<HashSet<CollectionObject>>,
+    timeout: RefCell<Option<SourceId>>,
+    writing: Cell<bool>,
+    /// Whether something changed while writing
+    pending: Cell<bool>,
+    /// Number of the last started save
+    generation: Cell<u64>,
+    /// Number of the last save written to disk, so that a slow background save
+    /// can't overwrite a newer one
+    written: Arc<Mutex<u64>>,
+}
+
+impl Autosave {
+    pub fn new(collections: &ListStore) -> Self {
+        let autosave = Self(Rc::new(Inner {
+            collections: collections.clone(),
+            watched_collections: Default::default(),
+            timeout: Default::default(),
+            writing: Default::default(),
+            pending: Default::default(),
+            generation: Default::default(),
+            written: Default::default(),
+        }));
+
+        let weak = Rc::downgrade(&autosave.0);
+        collections.connect_items_changed(move |collections, position, _, added| {
+            let Some(autosave) = weak.upgrade().map(Autosave) else {
+                return;
+            };
+            for c in (position..position + added).map(|i| {
+                collections
+                    .item(i)
+                    .and_downcast::<CollectionObject>()
+                    .unwrap()
+            }) {
+                autosave.watch(&c);
+            }
+            autosave.schedule();
+        });
+        for c in collections.iter::<CollectionObject>().map(Result::unwrap) {
+            autosave.watch(&c);
+        }
+
+        autosave
+    }
+
+    fn watch(&self, collection_object: &CollectionObject) {
+        if !self
+            .0
+            .watched_collections
+            .borrow_mut()
+            .insert(collection_object.clone())
+        {
+            return;
+        }
+
+        let weak = Rc::downgrade(&self.0);
+        collection_object.connect_notify_local(None, move |_, _| {
+            if let Some(autosave) = weak.upgrade() {
+                Autosave(autosave).schedule();
+            }
+        });
+        let weak = Rc::downgrade(&self.0);
+        collection_object.connect_tasks_changed(move |_| {
+            if let Some(autosave) = weak.upgrade() {
+                Autosave(autosave).schedule();
+            }
+        });
+        let weak = Rc::downgrade(&self.0);
+        collection_object.connect_task_notify(move |_, _, _| {
+            if let Some(autosave) = weak.upgrade() {
+                Autosave(autosave).schedule();
+            }
+        });
+    }
+
+    /// Save after `DELAY`, restarting the delay if a save is already scheduled
+    fn schedule(&self) {
+        if let Some(timeout) = self.0.timeout.take() {
+            timeout.remove();
+        }
+
+        let weak = Rc::downgrade(&self.0);
+        self.0
+            .timeout
+            .replace(Some(glib::timeout_add_local_once(DELAY, move || {
+                if let Some(autosave) = weak.upgrade() {
+                    autosave.timeout.take();
+                    Autosave(autosave).save_in_background();
+                }
+            })));
+    }
+
+    fn save_in_background(&self) {
+        if self.0.writing.get() {
+            self.0.pending.set(true);
+            return;
+        }
+        let contents = match storage::serialize(&self.0.collections) {
+            Ok(contents) => contents,
+            Err(err) => {
+                eprintln!("Error occurred trying to save collections: {err}");
+                return;
+            }
+        };
+
+        self.0.writing.set(true);
+        let generation = self.next_generation();
+        let written = self.0.written.clone();
+        let autosave = self.clone();
+        glib::spawn_future_local(async move {
+            let write = move || {
+                let mut written = written.lock().unwrap_or_else(|e| e.into_inner());
+                if *written > generation {
+                    return Ok(());
+                }
+                storage::write(&contents)?;
+                *written = generation;
+
+                Ok::<_, storage::Error>(())
+            };
+            match gio::spawn_blocking(write).await {
+                Ok(Err(err)) => eprintln!("Error occurred trying to save collections: {err}"),
+                Err(_) => eprintln!("Error occurred trying to save collections: writer panicked"),
+                Ok(Ok(())) => {}
+            }
+
+            autosave.0.writing.set(false);
+            if autosave.0.pending.take() {
+                autosave.schedule();
+            }
+        });
+    }
+
+    /// Save right away on the calling thread, dropping a scheduled save
+    pub fn save_now(&self) {
+        if let Some(timeout) = self.0.timeout.take() {
+            timeout.remove();
+        }
+        self.0.pending.set(false);
+
+        let generation = self.next_generation();
+        let mut written = self.0.written.lock().unwrap_or_else(|e| e.into_inner());
+        match storage::save(&self.0.collections) {
+            Ok(()) => *written = generation,
+            Err(err) => eprintln!("Error occurred trying to save collections: {err}"),
+        }
+    }
+
+    fn next_generation(&self) -> u64 {
+        self.0.generation.set(self.0.generation.get() + 1);
+
+        self.0.generation.get()
+    }
+}
@@ -1,7 +1,9 @@
+use std::collections::HashMap;
+
 use adw::subclass::prelude::ObjectSubclassIsExt;
 use gtk::{
     gio::prelude::ListModelExtManual,
-    glib::{self, Object},
+    glib::{self, Object, prelude::*},
 };
 use gtk_rs_test::list_store_ser::ListStoreSer;
 use serde::{
@@ -9,21 +11,79 @@ use serde::{
     de::{self, Visitor},
 };
 
-use crate::task_object::TaskObject;
+use crate::task_object::{self, TaskObject};
 
 glib::wrapper! {
     pub struct CollectionObject(ObjectSubclass<imp::CollectionObjectImp>);
 }
 
-pub type IdType = u64;
+/// Globally unique identifier of a collection, stored as a UUID string
+pub type IdType = String;
 
 impl CollectionObject {
     pub fn new(title: &str) -> Self {
         Object::builder().property("title", title).build()
     }
 
+    /// Create a collection with the id of an existing one, which `MainWindow` imports
+    /// tasks into
+    pub fn with_id(title: &str, id: IdType) -> Self {
+        let collection_object = Self::new(title);
+        *collection_object.imp().id.borrow_mut() = id;
+
+        collection_object
+    }
+
     pub fn get_id(&self) -> IdType {
-        self.imp().id
+        self.imp().id.borrow().clone()
+    }
+
+    /// Take over the stored fields and tasks of `other`, a newer state of the collection
+    ///
+    /// Tasks of `existing` with the id of one of the tasks are updated and kept.
+    pub fn update_from(
+        &self,
+        other: &CollectionObject,
+        existing: &HashMap<task_object::IdType, TaskObject>,
+    ) {
+        if self.title() != other.title() {
+            self.set_title(other.title());
+        }
+        if self.color() != other.color() {
+            self.set_color(other.color());
+        }
+        if self.icon() != other.icon() {
+            self.set_icon(other.icon());
+        }
+        task_object::update_tasks(&self.tasks(), &other.tasks(), existing);
+    }
+
+    /// Called with the task and the name of the property whenever a property of one of
+    /// the tasks or their subtasks changes
+    pub fn connect_task_notify<F: Fn(&Self, &TaskObject, &str) + 'static>(
+        &self,
+        f: F,
+    ) -> glib::SignalHandlerId {
+        self.connect_closure(
+            "task-notify",
+            false,
+            glib::closure_local!(move |collection_object: &Self,
+                                       task_object: &TaskObject,
+                                       property: &str| {
+                f(collection_object, task_object, property);
+            }),
+        )
+    }
+
+    /// Called whenever tasks or subtasks are added to or removed from the collection
+    pub fn connect_tasks_changed<F: Fn(&Self) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+        self.connect_closure(
+            "tasks-changed",
+            false,
+            glib::closure_local!(move |collection_object: &Self| {
+                f(collection_object);
+            }),
+        )
     }
 }
 
@@ -36,7 +96,7 @@ impl Serialize for CollectionObject {
     }
 }
 
-const FIELDS: [&'static str; 2] = ["title", "tasks"];
+const FIELDS: [&'static str; 5] = ["id", "title", "color", "icon", "tasks"];
 impl<'de> Deserialize<'de> for CollectionObject {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -55,25 +115,51 @@ impl<'de> Deserialize<'de> for CollectionObject {
             where
                 A: serde::de::MapAccess<'de>,
             {
+                let mut id = None;
                 let mut title = None;
+                let mut color = None;
+                let mut icon = None;
                 let mut tasks = None;
-                for _ in 0..2 {
-                    match map.next_key::<String>()?.unwrap().as_str() {
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "id" => {
+                            id = Some(map.next_value::<IdType>()?);
+                        }
                         "title" => {
                             title = Some(map.next_value::<String>()?);
                         }
+                        "color" => {
+                            color = Some(map.next_value::<String>()?);
+                        }
+                        "icon" => {
+                            icon = Some(map.next_value::<String>()?);
+                        }
                         "tasks" => {
                             tasks = Some(map.next_value::<ListStoreSer<TaskObject>>()?);
                         }
-                        f => return Err(de::Error::unknown_field(f, &FIELDS)),
+                        // Fields written by newer versions
+                        _ => {
+                            map.next_value::<de::IgnoredAny>()?;
+                        }
                     }
                 }
-                let title = title.ok_or_else(|| de::Error::missing_field("checked"))?;
+                let title = title.ok_or_else(|| de::Error::missing_field("title"))?;
                 let tasks = tasks
-                    .ok_or_else(|| de::Error::missing_field("checked"))?
+                    .ok_or_else(|| de::Error::missing_field("tasks"))?
                     .extract();
 
                 let collection_object = CollectionObject::new(&title);
+                // Both are missing from files written before collections could be styled
+                if let Some(color) = color {
+                    collection_object.set_color(color);
+                }
+                if let Some(icon) = icon {
+                    collection_object.set_icon(icon);
+                }
+                // Files written before ids were persisted keep the freshly generated one
+                if let Some(id) = id {
+                    *collection_object.imp().id.borrow_mut() = id;
+                }
                 let tasks_dest = collection_object.tasks();
                 for t in tasks.iter::<TaskObject>().map(Result::unwrap) {
                     tasks_dest.append(&t);
@@ -86,11 +172,15 @@ impl<'de> Deserialize<'de> for CollectionObject {
 }
 
 mod imp {
-    use std::{cell::RefCell, sync::Mutex};
+    use std::{
+        cell::RefCell,
+        collections::{HashMap, HashSet},
+        sync::OnceLock,
+    };
 
     use gtk::{
         gio::ListStore,
-        glib::{self, Properties},
+        glib::{self, Properties, SignalHandlerId, subclass::Signal},
     };
 
     use adw::prelude::*;
@@ -107,20 +197,101 @@ mod imp {
     pub struct CollectionObjectImp {
         #[property(get, set)]
         title: RefCell<String>,
+        /// Name of the accent color shown in the sidebar, such as `blue`, or empty
+        #[property(get, set)]
+        color: RefCell<String>,
+        /// Name of the symbolic icon shown in the sidebar, or empty
+        #[property(get, set)]
+        icon: RefCell<String>,
 
         /// ListStore storing TaskObject
         #[property(get)]
         tasks: ListStore,
 
-        pub(super) id: IdType,
+        pub(super) id: RefCell<IdType>,
+        /// Tasks and subtasks in the collection, with the handlers forwarding their
+        /// property changes and the changes of their subtasks
+        watched_tasks: RefCell<HashMap<TaskObject, (SignalHandlerId, SignalHandlerId)>>,
+    }
+    impl CollectionObjectImp {
+        /// Forward the changes of `tasks`, the top-level tasks of the collection
+        fn watch_tasks(&self, tasks: &ListStore) {
+            let collection_object = self.downgrade();
+            tasks.connect_items_changed(move |_, _, _, _| {
+                if let Some(this) = collection_object.upgrade() {
+                    this.tasks_changed();
+                }
+            });
+            self.update_watched_tasks();
+        }
+        fn tasks_changed(&self) {
+            self.update_watched_tasks();
+            self.obj().emit_by_name::<()>("tasks-changed", &[]);
+        }
+        /// Watch every task in the collection or any of their subtasks, and stop
+        /// watching removed ones, as they may live on in the undo history
+        fn update_watched_tasks(&self) {
+            fn collect(tasks: &ListStore, found: &mut HashSet<TaskObject>) {
+                for task_object in tasks.iter::<TaskObject>().map(Result::unwrap) {
+                    collect(&task_object.subtasks(), found);
+                    found.insert(task_object);
+                }
+            }
+            let mut current = HashSet::new();
+            collect(&self.tasks, &mut current);
+
+            let mut watched = self.watched_tasks.borrow_mut();
+            for (task_object, (notify, subtasks_changed)) in
+                watched.extract_if(|task_object, _| !current.contains(task_object))
+            {
+                task_object.disconnect(notify);
+                task_object.subtasks().disconnect(subtasks_changed);
+            }
+
+            for task_object in current {
+                if watched.contains_key(&task_object) {
+                    continue;
+                }
+
+                let collection_object = self.downgrade();
+                let notify = task_object.connect_notify_local(None, move |task_object, pspec| {
+                    let Some(collection_object) = collection_object.upgrade() else {
+                        return;
+                    };
+                    // Handlers of the signal may change the tasks
+                    let in_collection = collection_object
+                        .watched_tasks
+                        .borrow()
+                        .contains_key(task_object);
+                    if in_collection {
+                        collection_object
+                            .obj()
+                            .emit_by_name::<()>("task-notify", &[task_object, &pspec.name()]);
+                    }
+                });
+                let collection_object = self.downgrade();
+                let subtasks_changed =
+                    task_object
+                        .subtasks()
+                        .connect_items_changed(move |_, _, _, _| {
+                            if let Some(collection_object) = collection_object.upgrade() {
+                                collection_object.tasks_changed();
+                            }
+                        });
+                watched.insert(task_object, (notify, subtasks_changed));
+            }
+        }
     }
     impl Serialize for CollectionObjectImp {
         fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
         where
             S: Serializer,
         {
-            let mut stru = serializer.serialize_struct("CollectionObject", 2)?;
+            let mut stru = serializer.serialize_struct("CollectionObject", 5)?;
+            stru.serialize_field("id", &self.id)?;
             stru.serialize_field("title", &self.title)?;
+            stru.serialize_field("color", &self.color)?;
+            stru.serialize_field("icon", &self.icon)?;
             stru.serialize_field(
                 "tasks",
                 &ListStoreSer::<TaskObject>::new(self.tasks.clone()),
@@ -131,16 +302,14 @@ mod imp {
 
     impl Default for CollectionObjectImp {
         fn default() -> Self {
-            static ID: Mutex<IdType> = Mutex::new(0);
-            let mut id = ID.lock().unwrap();
-            let self_ = Self {
+            Self {
                 title: Default::default(),
+                color: Default::default(),
+                icon: Default::default(),
                 tasks: ListStore::new::<TaskObject>(),
-                id: *id,
-            };
-            *id += 1;
-
-            self_
+                id: RefCell::new(glib::uuid_string_random().into()),
+                watched_tasks: Default::default(),
+            }
         }
     }
 
@@ -151,5 +320,23 @@ mod imp {
     }
 
     #[glib::derived_properties]
-    impl ObjectImpl for CollectionObjectImp {}
+    impl ObjectImpl for CollectionObjectImp {
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+            SIGNALS.get_or_init(|| {
+                vec![
+                    Signal::builder("task-notify")
+                        .param_types([TaskObject::static_type(), String::static_type()])
+                        .build(),
+                    Signal::builder("tasks-changed").build(),
+                ]
+            })
+        }
+
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            self.watch_tasks(&self.tasks);
+        }
+    }
 }
@@ -0,0 +1,196 @@
+use std::fmt::Display;
+
+use gtk::glib::{self, DateTime, TimeZone};
+use serde::{Deserialize, Serialize, de};
+
+/// Deadline of a task: a calendar day with an optional time of day
+///
+/// Serialized as `2025-03-14` when no time is set, and as an ISO 8601 date time
+/// followed by the timezone identifier otherwise, e.g.
+/// `2025-03-14T17:30:00+01:00[Europe/Berlin]`.
+#[derive(Clone, Debug, PartialEq, glib::Boxed)]
+#[boxed_type(name = "GtkRsTestDueDate", nullable)]
+pub struct DueDate {
+    date_time: DateTime,
+    has_time: bool,
+}
+
+impl DueDate {
+    /// Due on the given day in the local timezone, stored as its start (00:00)
+    ///
+    /// Date-only due dates are compared by calendar day, see `is_overdue`.
+    pub fn from_ymd(year: i32, month: i32, day: i32) -> Option<Self> {
+        DateTime::new(&TimeZone::local(), year, month, day, 0, 0, 0.0)
+            .ok()
+            .map(|date_time| Self {
+                date_time,
+                has_time: false,
+            })
+    }
+
+    /// Due at an exact point in time, keeping the timezone of `date_time`
+    pub fn from_date_time(date_time: DateTime) -> Self {
+        Self {
+            date_time,
+            has_time: true,
+        }
+    }
+
+    /// Due on the day that is `days` away from today, see `from_ymd`
+    pub fn in_days(days: i32) -> Option<Self> {
+        let day = DateTime::now_local().ok()?.add_days(days).ok()?;
+        let (year, month, day) = day.ymd();
+
+        Self::from_ymd(year, month, day)
+    }
+
+    pub fn date_time(&self) -> &DateTime {
+        &self.date_time
+    }
+
+    pub fn has_time(&self) -> bool {
+        self.has_time
+    }
+
+    pub fn ymd(&self) -> (i32, i32, i32) {
+        self.date_time.ymd()
+    }
+
+    /// Whether the deadline has already passed
+    pub fn is_overdue(&self) -> bool {
+        let Ok(now) = DateTime::now_local() else {
+            return false;
+        };
+
+        if self.has_time {
+            self.date_time < now
+        } else {
+            self.days_from_today() < 0
+        }
+    }
+
+    /// Number of calendar days between today and the due day in the local timezone
+    pub fn days_from_today(&self) -> i32 {
+        let day_start = |date_time: &DateTime| {
+            let (year, month, day) = date_time
+                .to_local()
+                .map(|d| d.ymd())
+                .unwrap_or_else(|_| date_time.ymd());
+            DateTime::new(&TimeZone::local(), year, month, day, 0, 0, 0.0).ok()
+        };
+
+        match (
+            day_start(&self.date_time),
+            DateTime::now_local().ok().as_ref().and_then(day_start),
+        ) {
+            (Some(due), Some(today)) => {
+                (due.difference(&today).as_seconds() as f64 / 86_400.0).round() as i32
+            }
+            _ => 0,
+        }
+    }
+
+    /// Human readable description used in the task list, e.g. "Due tomorrow, 14:00"
+    pub fn label(&self) -> String {
+        let day = match self.days_from_today() {
+            -1 => String::from("yesterday"),
+            0 => String::from("today"),
+            1 => String::from("tomorrow"),
+            2..7 => self.format("%A"),
+            _ => {
+                let same_year = DateTime::now_local()
+                    .map(|now| now.year() == self.date_time.year())
+                    .unwrap_or(true);
+                self.format(if same_year { "%e %b" } else { "%e %b %Y" })
+                    .trim()
+                    .to_string()
+            }
+        };
+
+        if self.has_time {
+            format!("Due {day}, {}", self.format("%H:%M"))
+        } else {
+            format!("Due {day}")
+        }
+    }
+
+    fn format(&self, format: &str) -> String {
+        self.date_time
+            .to_local()
+            .and_then(|d| d.format(format))
+            .map(String::from)
+            .unwrap_or_default()
+    }
+
+    /// Parse the format produced by the `Display` implementation
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+
+        if !text.contains('T') {
+            let mut parts = text.splitn(3, '-').map(str::parse::<i32>);
+            return match (parts.next(), parts.next(), parts.next()) {
+                (Some(Ok(year)), Some(Ok(month)), Some(Ok(day))) => {
+                    Self::from_ymd(year, month, day)
+                }
+                _ => None,
+            };
+        }
+
+        let (date_time, timezone) = match text.split_once('[') {
+            Some((date_time, rest)) => (date_time, rest.strip_suffix(']')),
+            None => (text, None),
+        };
+        let mut date_time = DateTime::from_iso8601(date_time, Some(&TimeZone::local())).ok()?;
+        // Unknown identifiers fall back to UTC in GLib, keep the parsed offset in that case
+        if let Some(timezone) = timezone
+            .map(|t| (t, TimeZone::new(Some(t))))
+            .filter(|(t, timezone)| timezone.identifier() == *t)
+            .map(|(_, timezone)| timezone)
+        {
+            date_time = date_time.to_timezone(&timezone).ok()?;
+        }
+
+        Some(Self::from_date_time(date_time))
+    }
+}
+
+impl Display for DueDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.has_time {
+            let (year, month, day) = self.date_time.ymd();
+            return write!(f, "{year:04}-{month:02}-{day:02}");
+        }
+
+        let iso = self
+            .date_time
+            .format("%Y-%m-%dT%H:%M:%S%:z")
+            .map_err(|_| std::fmt::Error)?;
+        let timezone = self.date_time.timezone().identifier();
+        if timezone.is_empty() || timezone.starts_with(['+', '-']) {
+            write!(f, "{iso}")
+        } else {
+            write!(f, "{iso}[{timezone}]")
+        }
+    }
+}
+
+impl Serialize for DueDate {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for DueDate {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let text = String::deserialize(deserializer)?;
+
+        DueDate::parse(&text)
+            .ok_or_else(|| de::Error::custom(format!("invalid due date \"{text}\"")))
+    }
+}
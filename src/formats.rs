@@ -0,0 +1,171 @@
+use gtk::{
+    gio::{ListStore, prelude::*},
+    glib::{DateTime, TimeZone},
+};
+
+use crate::{collection_object::CollectionObject, task_object::TaskObject};
+
+pub mod csv;
+mod icalendar;
+pub mod markdown;
+mod org;
+mod taskwarrior;
+mod todo_txt;
+
+/// Errors of reading an imported file
+pub type Error = Box<dyn std::error::Error>;
+
+/// Format of other task managers that collections can be imported from and exported to
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    TodoTxt,
+    ICalendar,
+    Csv,
+    Taskwarrior,
+    Org,
+}
+
+impl Format {
+    pub const ALL: [Format; 5] = [
+        Format::TodoTxt,
+        Format::ICalendar,
+        Format::Csv,
+        Format::Taskwarrior,
+        Format::Org,
+    ];
+
+    /// Name shown to the user
+    pub fn label(self) -> &'static str {
+        match self {
+            Format::TodoTxt => "todo.txt",
+            Format::ICalendar => "iCalendar",
+            Format::Csv => "CSV",
+            Format::Taskwarrior => "Taskwarrior",
+            Format::Org => "Org Mode",
+        }
+    }
+
+    /// File name suggested when exporting
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Format::TodoTxt => "todo.txt",
+            Format::ICalendar => "tasks.ics",
+            Format::Csv => "tasks.csv",
+            Format::Taskwarrior => "tasks.json",
+            Format::Org => "tasks.org",
+        }
+    }
+
+    /// File name suffixes of files in the format, without the dot
+    pub fn suffixes(self) -> &'static [&'static str] {
+        match self {
+            Format::TodoTxt => &["txt"],
+            Format::ICalendar => &["ics"],
+            Format::Csv => &["csv"],
+            Format::Taskwarrior => &["json"],
+            Format::Org => &["org"],
+        }
+    }
+
+    /// Whether imported collections are merged into existing collections of the same
+    /// title, rather than always added as new ones
+    pub fn merges_collections(self) -> bool {
+        match self {
+            Format::TodoTxt | Format::Taskwarrior | Format::Org => true,
+            Format::ICalendar | Format::Csv => false,
+        }
+    }
+
+    /// Convert `collections` into the content of a file
+    pub fn export(self, collections: &[CollectionObject]) -> String {
+        match self {
+            Format::TodoTxt => todo_txt::export(collections),
+            Format::ICalendar => icalendar::export(collections),
+            Format::Csv => csv::export(collections),
+            Format::Taskwarrior => taskwarrior::export(collections),
+            Format::Org => org::export(collections),
+        }
+    }
+
+    /// Read the collections from the content of a file
+    ///
+    /// Tasks the file doesn't assign to a collection are put into one titled
+    /// `default_title`. CSV files are read with `csv::parse` instead, as the user maps
+    /// their columns.
+    pub fn import(self, text: &str, default_title: &str) -> Result<Vec<CollectionObject>, Error> {
+        match self {
+            Format::TodoTxt => Ok(todo_txt::import(text, default_title)),
+            Format::ICalendar => icalendar::import(text, default_title),
+            Format::Csv => Err("CSV columns have to be mapped, see `csv::parse`".into()),
+            Format::Taskwarrior => taskwarrior::import(text, default_title),
+            Format::Org => Ok(org::import(text, default_title)),
+        }
+    }
+}
+
+impl From<&str> for Format {
+    fn from(value: &str) -> Self {
+        match value {
+            "todo-txt" => Format::TodoTxt,
+            "icalendar" => Format::ICalendar,
+            "csv" => Format::Csv,
+            "taskwarrior" => Format::Taskwarrior,
+            "org" => Format::Org,
+            _ => panic!("Impossible"),
+        }
+    }
+}
+
+impl From<Format> for &str {
+    fn from(value: Format) -> Self {
+        match value {
+            Format::TodoTxt => "todo-txt",
+            Format::ICalendar => "icalendar",
+            Format::Csv => "csv",
+            Format::Taskwarrior => "taskwarrior",
+            Format::Org => "org",
+        }
+    }
+}
+
+/// `tasks` and their subtasks, each task followed by its subtasks
+fn flatten(tasks: &ListStore) -> Vec<TaskObject> {
+    let mut flat = Vec::new();
+    for task_object in tasks.iter::<TaskObject>().map(Result::unwrap) {
+        let subtasks = task_object.subtasks();
+        flat.push(task_object);
+        flat.extend(flatten(&subtasks));
+    }
+
+    flat
+}
+
+/// Format the day of `date_time` in the local timezone as `2025-03-14`
+fn format_date(date_time: &DateTime) -> String {
+    let (year, month, day) = date_time
+        .to_local()
+        .map(|d| d.ymd())
+        .unwrap_or_else(|_| date_time.ymd());
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Parse a day written as `2025-03-14`, returning its start in the local timezone
+fn parse_date(text: &str) -> Option<DateTime> {
+    let mut parts = text.splitn(3, '-');
+    let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return None;
+    }
+
+    DateTime::new(
+        &TimeZone::local(),
+        year.parse().ok()?,
+        month.parse().ok()?,
+        day.parse().ok()?,
+        0,
+        0,
+        0.0,
+    )
+    .ok()
+}
@@ -0,0 +1,288 @@
+use super::{Error, flatten, parse_date};
+use crate::{
+    collection_object::CollectionObject,
+    due_date::DueDate,
+    priority::Priority,
+    task_object::{self, TaskObject, format_timestamp, parse_timestamp},
+};
+
+/// Property of a task that a column of an imported CSV file is read into
+#[derive(Clone, Copy, PartialEq)]
+pub enum Column {
+    Ignore,
+    Collection,
+    Name,
+    Checked,
+    Notes,
+    Due,
+    Priority,
+    Tags,
+    Created,
+    Modified,
+    Completed,
+}
+
+impl Column {
+    pub const ALL: [Column; 11] = [
+        Column::Ignore,
+        Column::Collection,
+        Column::Name,
+        Column::Checked,
+        Column::Notes,
+        Column::Due,
+        Column::Priority,
+        Column::Tags,
+        Column::Created,
+        Column::Modified,
+        Column::Completed,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Column::Ignore => "Ignore",
+            Column::Collection => "Collection",
+            Column::Name => "Name",
+            Column::Checked => "Done",
+            Column::Notes => "Notes",
+            Column::Due => "Due Date",
+            Column::Priority => "Priority",
+            Column::Tags => "Tags",
+            Column::Created => "Created",
+            Column::Modified => "Modified",
+            Column::Completed => "Completed",
+        }
+    }
+
+    /// Column whose header is `header`, recognizing the headers written by `export` and
+    /// common alternatives
+    pub fn guess(header: &str) -> Column {
+        match header.trim().to_lowercase().as_str() {
+            "collection" | "project" | "list" => Column::Collection,
+            "name" | "title" | "task" | "summary" => Column::Name,
+            "checked" | "done" | "status" => Column::Checked,
+            "notes" | "description" | "details" => Column::Notes,
+            "due" | "due date" => Column::Due,
+            "priority" => Column::Priority,
+            "tags" | "labels" | "contexts" => Column::Tags,
+            "created" | "created at" => Column::Created,
+            "modified" | "modified at" => Column::Modified,
+            "completed" | "completed at" => Column::Completed,
+            _ => Column::Ignore,
+        }
+    }
+}
+
+/// Collections as CSV with a header row and a row per task
+///
+/// Subtasks follow their parent task as tasks of their own.
+pub fn export(collections: &[CollectionObject]) -> String {
+    let mut text = row([
+        "collection",
+        "name",
+        "checked",
+        "notes",
+        "due",
+        "priority",
+        "tags",
+        "created",
+        "modified",
+        "completed",
+    ]);
+    for collection_object in collections {
+        for task_object in flatten(&collection_object.tasks()) {
+            text.push_str(&row([
+                collection_object.title().as_str(),
+                &task_object.name(),
+                &task_object.checked().to_string(),
+                &task_object.notes(),
+                &task_object.due().map(|d| d.to_string()).unwrap_or_default(),
+                task_object.priority().into(),
+                &task_object.tags().join(", "),
+                &task_object
+                    .created_at()
+                    .as_ref()
+                    .map(format_timestamp)
+                    .unwrap_or_default(),
+                &task_object
+                    .modified_at()
+                    .as_ref()
+                    .map(format_timestamp)
+                    .unwrap_or_default(),
+                &task_object
+                    .completed_at()
+                    .as_ref()
+                    .map(format_timestamp)
+                    .unwrap_or_default(),
+            ]));
+        }
+    }
+
+    text
+}
+
+/// A line of CSV, quoting the fields that need it
+fn row<'a>(fields: impl IntoIterator<Item = &'a str>) -> String {
+    let fields: Vec<String> = fields
+        .into_iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect();
+
+    fields.join(",") + "\r\n"
+}
+
+/// Split CSV into its rows of fields
+///
+/// Fields are separated by commas, or by semicolons if the first line has more of
+/// them, as written by spreadsheets in locales using the comma as decimal separator.
+pub fn parse(text: &str) -> Result<Vec<Vec<String>>, Error> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let first_line = text.lines().next().unwrap_or_default();
+    let separator = if first_line.matches(';').count() > first_line.matches(',').count() {
+        ';'
+    } else {
+        ','
+    };
+
+    let mut rows = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.next_if_eq(&'"').is_some() {
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            _ if quoted => field.push(c),
+            '\r' | '\n' => {
+                if c == '\r' {
+                    chars.next_if_eq(&'\n');
+                }
+                fields.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut fields));
+            }
+            _ if c == separator => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("Unterminated quoted field".into());
+    }
+    if !field.is_empty() || !fields.is_empty() {
+        fields.push(field);
+        rows.push(fields);
+    }
+    // Spreadsheets tend to leave empty rows at the end
+    rows.retain(|fields| fields.iter().any(|f| !f.trim().is_empty()));
+
+    Ok(rows)
+}
+
+/// Create a task per row, reading each field into the property of its column
+///
+/// Each task comes with the title of its collection, empty if the row has none. Rows
+/// without a name are skipped.
+pub fn tasks(rows: &[Vec<String>], columns: &[Column]) -> Vec<(String, TaskObject)> {
+    rows.iter()
+        .filter_map(|fields| {
+            let field = |column: Column| {
+                columns
+                    .iter()
+                    .zip(fields)
+                    .filter(|(c, f)| **c == column && !f.trim().is_empty())
+                    .map(|(_, f)| f.trim())
+                    .next()
+            };
+
+            let task_object = TaskObject::new(field(Column::Name)?);
+            if let Some(checked) = field(Column::Checked) {
+                task_object.set_checked(parse_bool(checked));
+            }
+            if let Some(notes) = field(Column::Notes) {
+                task_object.set_notes(notes);
+            }
+            task_object.set_due(field(Column::Due).and_then(DueDate::parse));
+            if let Some(priority) = field(Column::Priority) {
+                task_object.set_priority(Priority::from(priority.to_lowercase().as_str()));
+            }
+            if let Some(tags) = field(Column::Tags) {
+                task_object.set_tags(task_object::parse_tags(tags));
+            }
+            // Restore the timestamps last, as setting the fields above touches them
+            let timestamp = |column: Column| {
+                field(column).and_then(|f| parse_timestamp(f).or_else(|| parse_date(f)))
+            };
+            if let Some(created) = timestamp(Column::Created) {
+                task_object.set_created_at(Some(created));
+            }
+            if let Some(modified) = timestamp(Column::Modified) {
+                task_object.set_modified_at(Some(modified));
+            }
+            if let Some(completed) = timestamp(Column::Completed) {
+                task_object.set_completed_at(Some(completed));
+            }
+
+            let collection = field(Column::Collection).unwrap_or_default();
+            Some((collection.to_string(), task_object))
+        })
+        .collect()
+}
+
+/// Whether a field of the `Done` column means the task is done
+fn parse_bool(text: &str) -> bool {
+    matches!(
+        text.to_lowercase().as_str(),
+        "true" | "yes" | "y" | "x" | "1" | "done" | "completed"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_round_trips() {
+        let task_object = TaskObject::new("Call \"Mom\", maybe");
+        task_object.set_notes("Ask about\nthe holidays");
+        task_object.set_priority(Priority::High);
+        task_object.set_tags(vec![String::from("family"), String::from("phone")]);
+        task_object.set_due(DueDate::from_ymd(2025, 3, 15));
+        let collection_object = CollectionObject::new("Family; friends");
+        collection_object.tasks().append(&task_object);
+
+        let rows = parse(&export(&[collection_object])).unwrap();
+        let (header, rows) = rows.split_first().unwrap();
+        let columns: Vec<Column> = header.iter().map(|h| Column::guess(h)).collect();
+        let tasks = tasks(rows, &columns);
+
+        assert_eq!(tasks.len(), 1);
+        let (collection, parsed) = &tasks[0];
+        assert_eq!(collection, "Family; friends");
+        assert_eq!(parsed.name(), task_object.name());
+        assert_eq!(parsed.notes(), task_object.notes());
+        assert_eq!(parsed.priority(), Priority::High);
+        assert_eq!(parsed.tags(), task_object.tags());
+        assert_eq!(parsed.due(), task_object.due());
+    }
+
+    #[test]
+    fn parses_semicolons_and_quotes() {
+        let rows = parse("\u{feff}Name;Done\r\n\"Buy milk; eggs\";yes\r\n;\r\n").unwrap();
+        assert_eq!(
+            rows,
+            vec![vec!["Name", "Done"], vec!["Buy milk; eggs", "yes"]]
+        );
+        assert!(parse("Name\n\"unterminated").is_err());
+    }
+}
@@ -0,0 +1,520 @@
+use std::collections::HashMap;
+
+use gtk::{
+    gio::prelude::*,
+    glib::{DateTime, TimeZone},
+};
+
+use super::{Error, flatten};
+use crate::{
+    collection_object::CollectionObject,
+    due_date::DueDate,
+    priority::Priority,
+    task_object::{self, TaskObject},
+};
+
+/// Longest line in octets, longer ones are folded
+const MAX_LINE_LENGTH: usize = 75;
+
+/// Collections as an iCalendar file with a VTODO component per task
+///
+/// Subtasks refer to their parent task by `RELATED-TO`. When a single collection is
+/// exported, its title is written as the name of the calendar.
+pub fn export(collections: &[CollectionObject]) -> String {
+    let mut lines = vec![
+        String::from("BEGIN:VCALENDAR"),
+        String::from("VERSION:2.0"),
+        String::from("PRODID:-//gtk-rs-test//To-Do List//EN"),
+    ];
+    if let [collection_object] = collections {
+        lines.push(format!(
+            "X-WR-CALNAME:{}",
+            escape(&collection_object.title())
+        ));
+    }
+
+    let now = DateTime::now_utc().ok();
+    for collection_object in collections {
+        let mut parents = HashMap::new();
+        for task_object in flatten(&collection_object.tasks()) {
+            for subtask in task_object
+                .subtasks()
+                .iter::<TaskObject>()
+                .map(Result::unwrap)
+            {
+                parents.insert(subtask.get_id(), task_object.get_id());
+            }
+            vtodo(
+                &task_object,
+                parents.get(&task_object.get_id()),
+                now.as_ref(),
+                &mut lines,
+            );
+        }
+    }
+    lines.push(String::from("END:VCALENDAR"));
+
+    lines.iter().map(|line| fold(line)).collect()
+}
+
+fn vtodo(
+    task_object: &TaskObject,
+    parent: Option<&task_object::IdType>,
+    now: Option<&DateTime>,
+    lines: &mut Vec<String>,
+) {
+    lines.push(String::from("BEGIN:VTODO"));
+    lines.push(format!("UID:{}", task_object.get_id()));
+    lines.extend(now.map(|now| format!("DTSTAMP:{}", format_utc(now))));
+    lines.extend(
+        task_object
+            .created_at()
+            .map(|created| format!("CREATED:{}", format_utc(&created))),
+    );
+    lines.extend(
+        task_object
+            .modified_at()
+            .map(|modified| format!("LAST-MODIFIED:{}", format_utc(&modified))),
+    );
+    lines.push(format!("SUMMARY:{}", escape(&task_object.name())));
+    if !task_object.notes().is_empty() {
+        lines.push(format!("DESCRIPTION:{}", escape(&task_object.notes())));
+    }
+    if task_object.checked() {
+        lines.push(String::from("STATUS:COMPLETED"));
+        lines.extend(
+            task_object
+                .completed_at()
+                .map(|completed| format!("COMPLETED:{}", format_utc(&completed))),
+        );
+    } else {
+        lines.push(String::from("STATUS:NEEDS-ACTION"));
+    }
+    if let Some(due) = task_object.due() {
+        if due.has_time() {
+            lines.push(format!("DUE:{}", format_utc(due.date_time())));
+        } else {
+            let (year, month, day) = due.ymd();
+            lines.push(format!("DUE;VALUE=DATE:{year:04}{month:02}{day:02}"));
+        }
+    }
+    if let Some(priority) = priority_number(task_object.priority()) {
+        lines.push(format!("PRIORITY:{priority}"));
+    }
+    if !task_object.tags().is_empty() {
+        let tags: Vec<String> = task_object.tags().iter().map(|t| escape(t)).collect();
+        lines.push(format!("CATEGORIES:{}", tags.join(",")));
+    }
+    if let Some(parent) = parent {
+        lines.push(format!("RELATED-TO;RELTYPE=PARENT:{parent}"));
+    }
+    lines.push(String::from("END:VTODO"));
+}
+
+/// Read the tasks of an iCalendar file into a new collection
+///
+/// The collection is titled by the name of the calendar, or `default_title` if it has
+/// none.
+pub fn import(text: &str, default_title: &str) -> Result<Vec<CollectionObject>, Error> {
+    let lines = unfold(text);
+    if lines.first().map(|l| l.trim()) != Some("BEGIN:VCALENDAR") {
+        return Err("not an iCalendar file".into());
+    }
+
+    let mut title = None;
+    // Tasks with their UID and the UID of their parent
+    let mut tasks: Vec<(Option<String>, Option<String>, TaskObject)> = Vec::new();
+    // Properties of the VTODO being read, by name
+    let mut vtodo: Option<Vec<Property>> = None;
+    // Components nested in the VTODO such as alarms, whose properties are skipped
+    let mut nested: u32 = 0;
+
+    for line in &lines {
+        let Some(property) = Property::parse(line) else {
+            continue;
+        };
+        match (
+            property.name.as_str(),
+            property.value.as_str(),
+            vtodo.is_some(),
+        ) {
+            ("BEGIN", "VTODO", false) => vtodo = Some(Vec::new()),
+            ("END", "VTODO", true) if nested == 0 => tasks.push(task(&vtodo.take().unwrap())),
+            ("BEGIN", _, true) => nested += 1,
+            ("END", _, true) => nested = nested.saturating_sub(1),
+            (_, _, true) if nested == 0 => vtodo.as_mut().unwrap().push(property),
+            ("X-WR-CALNAME", value, false) => title = Some(unescape(value)),
+            _ => {}
+        }
+    }
+
+    let collection_object = CollectionObject::new(title.as_deref().unwrap_or(default_title));
+    let by_uid: HashMap<&str, (Option<&str>, &TaskObject)> = tasks
+        .iter()
+        .filter_map(|(uid, parent, task_object)| {
+            Some((uid.as_deref()?, (parent.as_deref(), task_object)))
+        })
+        .collect();
+    // Whether following the parents of the task with `uid` leads back to it
+    let in_cycle = |uid: &str| {
+        let mut current = uid;
+        for _ in 0..by_uid.len() {
+            match by_uid.get(current).and_then(|(parent, _)| *parent) {
+                Some(parent) if parent == uid => return true,
+                Some(parent) => current = parent,
+                None => return false,
+            }
+        }
+        false
+    };
+    for (uid, parent, task_object) in &tasks {
+        match parent.as_deref().and_then(|p| by_uid.get(p)) {
+            Some((_, parent)) if !uid.as_deref().is_some_and(in_cycle) => {
+                parent.subtasks().append(task_object)
+            }
+            _ => collection_object.tasks().append(task_object),
+        }
+    }
+
+    Ok(vec![collection_object])
+}
+
+/// Build the task described by the properties of a VTODO
+///
+/// Returns the task together with its UID and the UID of its parent task.
+fn task(properties: &[Property]) -> (Option<String>, Option<String>, TaskObject) {
+    let get = |name: &str| properties.iter().find(|p| p.name == name);
+
+    let task_object = TaskObject::new(
+        &get("SUMMARY")
+            .map(|p| unescape(&p.value))
+            .unwrap_or_default(),
+    );
+    task_object.set_notes(
+        get("DESCRIPTION")
+            .map(|p| unescape(&p.value))
+            .unwrap_or_default(),
+    );
+    let completed = get("COMPLETED").and_then(|p| p.date_time());
+    task_object
+        .set_checked(get("STATUS").is_some_and(|p| p.value == "COMPLETED") || completed.is_some());
+    task_object.set_due(get("DUE").and_then(Property::due_date));
+    task_object.set_priority(
+        get("PRIORITY")
+            .and_then(|p| p.value.trim().parse().ok())
+            .map_or(Priority::None, parse_priority_number),
+    );
+    // Categories may contain spaces or commas, they are taken as they are
+    let mut tags: Vec<String> = Vec::new();
+    for tag in properties
+        .iter()
+        .filter(|p| p.name == "CATEGORIES")
+        .flat_map(|p| split_list(&p.value))
+    {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    task_object.set_tags(tags);
+
+    // Restore the timestamps last, as setting the fields above touches them
+    let created = get("CREATED").and_then(|p| p.date_time());
+    if created.is_some() {
+        task_object.set_created_at(created);
+    }
+    if completed.is_some() {
+        task_object.set_completed_at(completed);
+    }
+    if let Some(modified) = get("LAST-MODIFIED").and_then(|p| p.date_time()) {
+        task_object.set_modified_at(Some(modified));
+    }
+
+    let uid = get("UID").map(|p| p.value.clone());
+    // Only a parent relation counts, which is the default type
+    let parent = properties
+        .iter()
+        .find(|p| {
+            p.name == "RELATED-TO"
+                && p.parameter("RELTYPE")
+                    .is_none_or(|t| t.eq_ignore_ascii_case("PARENT"))
+        })
+        .map(|p| p.value.clone());
+
+    (uid, parent, task_object)
+}
+
+/// Content line such as `DUE;TZID=Europe/Berlin:20250314T173000`
+struct Property {
+    name: String,
+    parameters: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn parse(line: &str) -> Option<Self> {
+        // The value starts at the first colon outside of a quoted parameter value
+        let mut quoted = false;
+        let colon = line.char_indices().find_map(|(i, c)| match c {
+            '"' => {
+                quoted = !quoted;
+                None
+            }
+            ':' if !quoted => Some(i),
+            _ => None,
+        })?;
+        let (head, value) = (&line[..colon], &line[colon + 1..]);
+
+        let mut parts = head.split(';');
+        let name = parts.next()?.trim().to_ascii_uppercase();
+        let parameters = parts
+            .filter_map(|p| p.split_once('='))
+            .map(|(k, v)| (k.to_ascii_uppercase(), v.trim_matches('"').to_string()))
+            .collect();
+
+        Some(Self {
+            name,
+            parameters,
+            value: value.to_string(),
+        })
+    }
+
+    fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Value of a DATE-TIME property such as `CREATED`
+    fn date_time(&self) -> Option<DateTime> {
+        parse_date_time(&self.value, self.parameter("TZID"))
+    }
+
+    /// Value of `DUE`, which is either a DATE or a DATE-TIME
+    fn due_date(&self) -> Option<DueDate> {
+        let value = self.value.trim();
+        if self.parameter("VALUE") == Some("DATE") || !value.contains('T') {
+            let (year, month, day) = parse_ymd(value)?;
+            return DueDate::from_ymd(year, month, day);
+        }
+
+        parse_date_time(value, self.parameter("TZID")).map(DueDate::from_date_time)
+    }
+}
+
+/// Join folded lines, which continue on lines starting with a space or a tab
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continued), Some(last)) => last.push_str(continued),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    lines
+}
+
+/// Split `line` into lines of at most `MAX_LINE_LENGTH` octets, ending each with CRLF
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_LENGTH {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+
+    folded
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => {}
+        }
+    }
+
+    unescaped
+}
+
+/// Split a list of values separated by unescaped commas, e.g. of `CATEGORIES`
+fn split_list(text: &str) -> Vec<String> {
+    let mut values = vec![String::new()];
+    let mut escaped = false;
+    for c in text.chars() {
+        match c {
+            ',' if !escaped => values.push(String::new()),
+            _ => values.last_mut().unwrap().push(c),
+        }
+        escaped = c == '\\' && !escaped;
+    }
+
+    values
+        .iter()
+        .map(|v| unescape(v.trim()))
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+/// Format as a DATE-TIME in UTC, e.g. `20250314T163000Z`
+fn format_utc(date_time: &DateTime) -> String {
+    date_time
+        .to_utc()
+        .and_then(|d| d.format("%Y%m%dT%H%M%SZ"))
+        .map(String::from)
+        .unwrap_or_default()
+}
+
+fn parse_ymd(text: &str) -> Option<(i32, i32, i32)> {
+    if text.len() != 8 || !text.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    Some((
+        text[..4].parse().ok()?,
+        text[4..6].parse().ok()?,
+        text[6..].parse().ok()?,
+    ))
+}
+
+/// Parse a DATE-TIME, which is in UTC when ending with `Z`, in the timezone `tzid` if
+/// given and in the local timezone otherwise
+fn parse_date_time(text: &str, tzid: Option<&str>) -> Option<DateTime> {
+    let text = text.trim();
+    let (date, time) = text.split_once('T')?;
+    let (year, month, day) = parse_ymd(date)?;
+    let (time, utc) = match time.strip_suffix('Z') {
+        Some(time) => (time, true),
+        None => (time, false),
+    };
+    if time.len() != 6 || !time.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let timezone = match tzid {
+        _ if utc => TimeZone::utc(),
+        // Unknown identifiers fall back to UTC in GLib, use the local timezone instead
+        Some(tzid) => Some(TimeZone::new(Some(tzid)))
+            .filter(|t| t.identifier() == tzid)
+            .unwrap_or_else(TimeZone::local),
+        None => TimeZone::local(),
+    };
+
+    DateTime::new(
+        &timezone,
+        year,
+        month,
+        day,
+        time[..2].parse().ok()?,
+        time[2..4].parse().ok()?,
+        time[4..].parse().ok()?,
+    )
+    .ok()
+}
+
+/// Priority as the number used by iCalendar, from 1 for the highest to 9 for the lowest
+fn priority_number(priority: Priority) -> Option<u8> {
+    match priority {
+        Priority::None => None,
+        Priority::Low => Some(9),
+        Priority::Medium => Some(5),
+        Priority::High => Some(2),
+        Priority::Urgent => Some(1),
+    }
+}
+
+/// Map a priority number, where 1 to 4 are high, 5 is medium and 6 to 9 are low
+fn parse_priority_number(number: u8) -> Priority {
+    match number {
+        1 => Priority::Urgent,
+        2..=4 => Priority::High,
+        5 => Priority::Medium,
+        6..=9 => Priority::Low,
+        _ => Priority::None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calendar(vtodo: &str) -> String {
+        format!("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n{vtodo}END:VCALENDAR\r\n")
+    }
+
+    fn first_task(collections: &[CollectionObject]) -> TaskObject {
+        collections[0]
+            .tasks()
+            .item(0)
+            .and_downcast::<TaskObject>()
+            .unwrap()
+    }
+
+    #[test]
+    fn tasks_round_trip() {
+        let collection_object = CollectionObject::new("Family");
+        let task_object = TaskObject::new("Call Mom, then Dad");
+        task_object.set_tags(vec![String::from("phone"), String::from("after work")]);
+        task_object.set_notes("Ask about the holidays\nand the garden");
+        task_object.set_due(DueDate::from_ymd(2025, 3, 15));
+        let subtask = TaskObject::new("Book flights");
+        task_object.subtasks().append(&subtask);
+        collection_object.tasks().append(&task_object);
+
+        let collections = import(&export(&[collection_object]), "Imported").unwrap();
+        assert_eq!(collections[0].title(), "Family");
+        let parsed = first_task(&collections);
+        assert_eq!(parsed.name(), task_object.name());
+        assert_eq!(parsed.tags(), task_object.tags());
+        assert_eq!(parsed.notes(), task_object.notes());
+        assert_eq!(parsed.due(), task_object.due());
+        let subtask = parsed
+            .subtasks()
+            .item(0)
+            .and_downcast::<TaskObject>()
+            .unwrap();
+        assert_eq!(subtask.name(), "Book flights");
+    }
+
+    #[test]
+    fn categories_are_kept_as_they_are() {
+        let text = calendar(
+            "BEGIN:VTODO\r\nSUMMARY:Call Mom\r\nCATEGORIES:after work,#phone,a\\,b\r\n\
+             CATEGORIES:after work\r\nEND:VTODO\r\n",
+        );
+        let parsed = first_task(&import(&text, "Imported").unwrap());
+        assert_eq!(parsed.tags(), ["after work", "#phone", "a,b"]);
+    }
+
+    #[test]
+    fn unbalanced_components_are_skipped() {
+        let text = calendar(
+            "BEGIN:VTODO\r\nSUMMARY:Call Mom\r\nEND:VALARM\r\nEND:VTODO\r\n\
+             BEGIN:VTODO\r\nSUMMARY:Book flights\r\nEND:VTODO\r\n",
+        );
+        let collections = import(&text, "Imported").unwrap();
+        assert_eq!(collections[0].title(), "Imported");
+        assert_eq!(collections[0].tasks().n_items(), 2);
+        assert_eq!(first_task(&collections).name(), "Call Mom");
+    }
+}
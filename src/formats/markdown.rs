@@ -0,0 +1,141 @@
+use gtk::gio::{ListStore, prelude::*};
+
+use crate::task_object::{self, TaskObject};
+
+/// Indentation of a subtask relative to its parent task
+const INDENT: &str = "  ";
+
+/// Tasks as a Markdown checklist such as `- [x] Call Mom #family`, a line per task
+///
+/// Subtasks follow their parent task, indented by two more spaces.
+pub fn export(tasks: &ListStore) -> String {
+    let mut text = String::new();
+    write_tasks(tasks, 0, &mut text);
+
+    text
+}
+
+fn write_tasks(tasks: &ListStore, depth: usize, text: &mut String) {
+    for task_object in tasks.iter::<TaskObject>().map(Result::unwrap) {
+        let mark = if task_object.checked() { 'x' } else { ' ' };
+        text.push_str(&INDENT.repeat(depth));
+        text.push_str(&format!("- [{mark}] {}", task_object.name()));
+        for tag in task_object.tags() {
+            text.push_str(&format!(" #{tag}"));
+        }
+        text.push('\n');
+
+        write_tasks(&task_object.subtasks(), depth + 1, text);
+    }
+}
+
+/// Read the tasks of a Markdown checklist, see `export`
+///
+/// Headings and blank lines are skipped. Returns `None` if any other line is not an
+/// item of the checklist, or if there are no items at all.
+pub fn import(text: &str) -> Option<Vec<TaskObject>> {
+    let mut tasks = Vec::new();
+    // Open tasks with the indentation of their line, each nested in the one before
+    let mut parents: Vec<(usize, TaskObject)> = Vec::new();
+
+    for line in text.lines() {
+        let item = line.trim_start();
+        if item.is_empty() || item.starts_with('#') {
+            continue;
+        }
+        let indent = line[..line.len() - item.len()]
+            .chars()
+            .map(|c| if c == '\t' { 4 } else { 1 })
+            .sum();
+        let task_object = parse_item(item)?;
+
+        while parents.last().is_some_and(|(i, _)| *i >= indent) {
+            parents.pop();
+        }
+        match parents.last() {
+            Some((_, parent)) => parent.subtasks().append(&task_object),
+            None => tasks.push(task_object.clone()),
+        }
+        parents.push((indent, task_object));
+    }
+
+    if tasks.is_empty() { None } else { Some(tasks) }
+}
+
+/// Parse an item such as `- [ ] Buy milk #shopping`, also accepting `*` and `+` bullets
+fn parse_item(item: &str) -> Option<TaskObject> {
+    let rest = item
+        .strip_prefix(['-', '*', '+'])?
+        .strip_prefix(' ')?
+        .trim_start();
+    let checked = match rest.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+
+    // Words starting with `#` become tags, like when adding a task
+    let (tags, words): (Vec<&str>, Vec<&str>) = rest[3..].split_whitespace().partition(|word| {
+        word.strip_prefix('#')
+            .is_some_and(|tag| tag.chars().any(|c| !c.is_ascii_digit()))
+    });
+    if words.is_empty() {
+        return None;
+    }
+
+    let task_object = TaskObject::new(&words.join(" "));
+    task_object.set_tags(task_object::parse_tags(&tags.join(" ")));
+    task_object.set_checked(checked);
+
+    Some(task_object)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checklists_round_trip() {
+        let tasks = ListStore::new::<TaskObject>();
+        let task_object = TaskObject::new("Call Mom");
+        task_object.set_tags(vec![String::from("family")]);
+        let subtask = TaskObject::new("Book flights");
+        subtask.set_checked(true);
+        task_object.subtasks().append(&subtask);
+        tasks.append(&task_object);
+        tasks.append(&TaskObject::new("Buy milk"));
+
+        let text = export(&tasks);
+        assert_eq!(
+            text,
+            "- [ ] Call Mom #family\n  - [x] Book flights\n- [ ] Buy milk\n"
+        );
+        let parsed = import(&text).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].name(), "Call Mom");
+        assert_eq!(parsed[0].tags(), ["family"]);
+        let subtask = parsed[0]
+            .subtasks()
+            .item(0)
+            .and_downcast::<TaskObject>()
+            .unwrap();
+        assert_eq!(subtask.name(), "Book flights");
+        assert!(subtask.checked());
+        assert_eq!(parsed[1].name(), "Buy milk");
+    }
+
+    #[test]
+    fn numbers_stay_in_the_name() {
+        let parsed = import("* [X] Fix #1 #work").unwrap();
+        assert_eq!(parsed[0].name(), "Fix #1");
+        assert_eq!(parsed[0].tags(), ["work"]);
+        assert!(parsed[0].checked());
+    }
+
+    #[test]
+    fn other_text_is_no_checklist() {
+        assert!(import("# Groceries\n\n").is_none());
+        assert!(import("- [ ] Buy milk\nand bread").is_none());
+        assert!(import("- Buy milk").is_none());
+    }
+}
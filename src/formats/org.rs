@@ -0,0 +1,467 @@
+use gtk::{
+    gio::{ListStore, prelude::*},
+    glib::{DateTime, TimeZone},
+};
+
+use crate::{
+    collection_object::CollectionObject, due_date::DueDate, priority::Priority,
+    task_object::TaskObject,
+};
+
+/// Keywords of headings that are unresolved tasks, the first one is written on export
+const TODO_KEYWORDS: [&str; 5] = ["TODO", "NEXT", "STARTED", "WAITING", "HOLD"];
+/// Keywords of headings that are done tasks, the first one is written on export
+const DONE_KEYWORDS: [&str; 3] = ["DONE", "CANCELED", "CANCELLED"];
+
+/// Tag of collection headings which would otherwise be read as a task
+const COLLECTION_TAG: &str = "collection";
+
+/// Written after names which end like tags, so that they aren't read as tags
+const TAGS_GUARD: char = '\u{200B}';
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Collections as an [Org mode](https://orgmode.org/manual/) outline such as
+///
+/// ```org
+/// * Family
+/// ** DONE [#B] Call Mom :phone:
+///    CLOSED: [2025-03-14 Fri 10:05] DEADLINE: <2025-03-15 Sat>
+///    Ask about the holidays
+/// *** TODO Book flights
+/// ```
+///
+/// Each collection is a top-level heading with its tasks as child headings, the notes
+/// of a task are the text below its heading. Collection headings starting with a TODO
+/// keyword or ending in tags are tagged `:collection:`.
+///
+/// Org mode only has the priorities `A` to `C` by default, so urgent tasks are written
+/// as `[#A]` like high ones and read back as high.
+pub fn export(collections: &[CollectionObject]) -> String {
+    let mut text = String::new();
+    for collection_object in collections {
+        let title = collection_object.title();
+        if split_keyword(&title).0.is_some() || !split_tags(&title).1.is_empty() {
+            text.push_str(&format!("* {title} :{COLLECTION_TAG}:\n"));
+        } else {
+            text.push_str(&format!("* {title}\n"));
+        }
+        write_tasks(&collection_object.tasks(), 2, &mut text);
+    }
+
+    text
+}
+
+fn write_tasks(tasks: &ListStore, level: usize, text: &mut String) {
+    for task_object in tasks.iter::<TaskObject>().map(Result::unwrap) {
+        let keyword = if task_object.checked() {
+            DONE_KEYWORDS[0]
+        } else {
+            TODO_KEYWORDS[0]
+        };
+        let mut heading = format!("{} {keyword}", "*".repeat(level));
+        if let Some(p) = letter(task_object.priority()) {
+            heading.push_str(&format!(" [#{p}]"));
+        }
+        heading.push(' ');
+        let name = task_object.name();
+        heading.push_str(&name);
+        if !task_object.tags().is_empty() {
+            let tags: Vec<String> = task_object.tags().iter().map(|t| tag(t)).collect();
+            heading.push_str(&format!(" :{}:", tags.join(":")));
+        } else if !split_tags(&name).1.is_empty() {
+            heading.push(TAGS_GUARD);
+        }
+        text.push_str(&heading);
+        text.push('\n');
+
+        // The body is indented to line up with the title of the heading
+        let indent = " ".repeat(level + 1);
+        let mut planning = Vec::new();
+        if task_object.checked()
+            && let Some(completed) = task_object.completed_at()
+        {
+            planning.push(format!("CLOSED: [{}]", timestamp(&completed, true)));
+        }
+        if let Some(due) = task_object.due() {
+            planning.push(format!(
+                "DEADLINE: <{}>",
+                timestamp(due.date_time(), due.has_time())
+            ));
+        }
+        if !planning.is_empty() {
+            text.push_str(&format!("{indent}{}\n", planning.join(" ")));
+        }
+        for line in task_object.notes().lines() {
+            if !line.is_empty() {
+                text.push_str(&indent);
+            }
+            text.push_str(line);
+            text.push('\n');
+        }
+
+        write_tasks(&task_object.subtasks(), level + 1, text);
+    }
+}
+
+/// Org tags may only contain letters, numbers, `_`, `@`, `#` and `%`
+fn tag(tag: &str) -> String {
+    tag.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || "_@#%".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Format a timestamp such as `2025-03-14 Fri 17:30` in the local timezone
+fn timestamp(date_time: &DateTime, with_time: bool) -> String {
+    let local = date_time.to_local().unwrap_or_else(|_| date_time.clone());
+    let (year, month, day) = local.ymd();
+    let weekday = WEEKDAYS[local.day_of_week() as usize - 1];
+
+    let mut text = format!("{year:04}-{month:02}-{day:02} {weekday}");
+    if with_time {
+        text.push_str(&format!(" {:02}:{:02}", local.hour(), local.minute()));
+    }
+
+    text
+}
+
+/// Read the collections from an Org mode outline, see `export`
+///
+/// Top-level headings without a TODO keyword are collections, other headings are
+/// tasks nested in the task or collection above them. Tasks outside of any collection
+/// are put into one titled `default_title`. SCHEDULED dates are skipped, as tasks only
+/// have a deadline.
+pub fn import(text: &str, default_title: &str) -> Vec<CollectionObject> {
+    let mut collections: Vec<CollectionObject> = Vec::new();
+    let mut collection: Option<CollectionObject> = None;
+    // Tasks following headings may be nested in, with the level of their heading
+    let mut parents: Vec<(usize, TaskObject)> = Vec::new();
+    let mut notes: Vec<&str> = Vec::new();
+    let mut in_drawer = false;
+
+    let mut find_collection = |title: &str| match collections.iter().find(|c| c.title() == title) {
+        Some(c) => c.clone(),
+        None => {
+            let c = CollectionObject::new(title);
+            collections.push(c.clone());
+            c
+        }
+    };
+
+    for line in text.lines() {
+        if let Some((level, rest)) = parse_heading(line) {
+            if let Some((_, task_object)) = parents.last() {
+                set_notes(task_object, &notes);
+            }
+            notes.clear();
+            in_drawer = false;
+
+            let (title, tags) = split_tags(rest);
+            let (keyword, rest) = split_keyword(rest);
+            if level == 1 && (keyword.is_none() || tags.contains(&COLLECTION_TAG)) {
+                collection = Some(find_collection(title));
+                parents.clear();
+                continue;
+            }
+            let task_object = parse_task(keyword, rest);
+
+            while parents.last().is_some_and(|(l, _)| *l >= level) {
+                parents.pop();
+            }
+            match parents.last() {
+                Some((_, parent)) => parent.subtasks().append(&task_object),
+                None => {
+                    // Top-level tasks don't belong to the collection above them
+                    if level == 1 {
+                        collection = None;
+                    }
+                    collection
+                        .get_or_insert_with(|| find_collection(default_title))
+                        .tasks()
+                        .append(&task_object);
+                }
+            }
+            parents.push((level, task_object));
+            continue;
+        }
+
+        let Some((_, task_object)) = parents.last() else {
+            continue;
+        };
+        let trimmed = line.trim();
+        if in_drawer {
+            in_drawer = !trimmed.eq_ignore_ascii_case(":END:");
+        } else if trimmed.len() > 1
+            && trimmed.starts_with(':')
+            && trimmed.ends_with(':')
+            && !trimmed.contains(char::is_whitespace)
+        {
+            // Drawers such as `:PROPERTIES:` or `:LOGBOOK:` hold metadata, not notes
+            in_drawer = true;
+        } else if ["CLOSED:", "DEADLINE:", "SCHEDULED:"]
+            .iter()
+            .any(|k| trimmed.starts_with(k))
+        {
+            if let Some(deadline) = planning(trimmed, "DEADLINE:").and_then(parse_timestamp) {
+                task_object.set_due(Some(deadline));
+            }
+            if task_object.checked()
+                && let Some(closed) = planning(trimmed, "CLOSED:").and_then(parse_timestamp)
+            {
+                task_object.set_completed_at(Some(closed.date_time().clone()));
+            }
+        } else {
+            notes.push(line);
+        }
+    }
+    if let Some((_, task_object)) = parents.last() {
+        set_notes(task_object, &notes);
+    }
+
+    collections
+}
+
+/// Letter of a priority, Org mode only has `A` to `C` by default
+fn letter(priority: Priority) -> Option<char> {
+    match priority {
+        Priority::None => None,
+        Priority::Low => Some('C'),
+        Priority::Medium => Some('B'),
+        Priority::High | Priority::Urgent => Some('A'),
+    }
+}
+
+/// Parse a priority letter, letters of a custom range after `C` are low priorities
+fn parse_letter(text: &str) -> Option<Priority> {
+    match text {
+        "A" => Some(Priority::High),
+        "B" => Some(Priority::Medium),
+        _ if text.len() == 1 && text.chars().all(|c| c.is_ascii_uppercase()) => Some(Priority::Low),
+        _ => None,
+    }
+}
+
+/// Level and the text after the stars of a heading such as `** TODO Call Mom`
+fn parse_heading(line: &str) -> Option<(usize, &str)> {
+    let rest = line.trim_start_matches('*');
+    let level = line.len() - rest.len();
+    if level == 0 {
+        return None;
+    }
+
+    match rest.strip_prefix(' ') {
+        Some(rest) => Some((level, rest.trim())),
+        None if rest.is_empty() => Some((level, rest)),
+        None => None,
+    }
+}
+
+/// Split a TODO or DONE keyword off the text of a heading, telling whether it is done
+fn split_keyword(text: &str) -> (Option<bool>, &str) {
+    let (word, rest) = text.split_once(' ').unwrap_or((text, ""));
+    if TODO_KEYWORDS.contains(&word) {
+        (Some(false), rest.trim_start())
+    } else if DONE_KEYWORDS.contains(&word) {
+        (Some(true), rest.trim_start())
+    } else {
+        (None, text)
+    }
+}
+
+/// Split the tags such as `:phone:family:` off the end of the text of a heading
+fn split_tags(text: &str) -> (&str, Vec<&str>) {
+    let (title, last) = text.rsplit_once(' ').unwrap_or(("", text));
+    match last.strip_prefix(':').and_then(|t| t.strip_suffix(':')) {
+        Some(tags) if !tags.is_empty() => (
+            title.trim_end(),
+            tags.split(':').filter(|t| !t.is_empty()).collect(),
+        ),
+        _ => (text, Vec::new()),
+    }
+}
+
+/// Build the task of a heading, with the text after its keyword
+fn parse_task(checked: Option<bool>, text: &str) -> TaskObject {
+    let (priority, text) = match text
+        .strip_prefix("[#")
+        .and_then(|t| t.split_once(']'))
+        .and_then(|(p, rest)| Some((parse_letter(p)?, rest.trim_start())))
+    {
+        Some((priority, text)) => (priority, text),
+        None => (Priority::None, text),
+    };
+    let (name, tags) = split_tags(text);
+    let name = if name.is_empty() { text } else { name };
+
+    let task_object = TaskObject::new(name.strip_suffix(TAGS_GUARD).unwrap_or(name));
+    task_object.set_checked(checked.unwrap_or(false));
+    task_object.set_priority(priority);
+    task_object.set_tags(tags.into_iter().map(str::to_string).collect::<Vec<_>>());
+
+    task_object
+}
+
+/// Notes are the body of a heading with the indentation common to its lines removed
+fn set_notes(task_object: &TaskObject, lines: &[&str]) {
+    let indent = lines
+        .iter()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let notes: Vec<&str> = lines
+        .iter()
+        .map(|l| l.get(indent..).unwrap_or_default().trim_end())
+        .collect();
+
+    let notes = notes.join("\n");
+    let notes = notes.trim_matches('\n');
+    if !notes.is_empty() {
+        task_object.set_notes(notes);
+    }
+}
+
+/// Text of the timestamp following `keyword` in a planning line, without its brackets
+fn planning<'a>(line: &'a str, keyword: &str) -> Option<&'a str> {
+    let rest = line[line.find(keyword)? + keyword.len()..].trim_start();
+    let close = match rest.chars().next()? {
+        '<' => '>',
+        '[' => ']',
+        _ => return None,
+    };
+
+    Some(&rest[1..rest.find(close)?])
+}
+
+/// Parse a timestamp such as `2025-03-14 Fri 17:30`, the time being optional
+fn parse_timestamp(text: &str) -> Option<DueDate> {
+    let mut words = text.split_whitespace();
+    let mut date = words.next()?.splitn(3, '-').map(str::parse::<i32>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+
+    // Skip the weekday, and take the start of a time range such as `10:00-11:30`
+    let time = words
+        .find(|w| w.starts_with(|c: char| c.is_ascii_digit()))
+        .and_then(|w| w.split('-').next()?.split_once(':'));
+    match time {
+        Some((hour, minute)) => DateTime::new(
+            &TimeZone::local(),
+            year,
+            month,
+            day,
+            hour.parse().ok()?,
+            minute.parse().ok()?,
+            0.0,
+        )
+        .ok()
+        .map(DueDate::from_date_time),
+        None => DueDate::from_ymd(year, month, day),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collection(title: &str, tasks: &[TaskObject]) -> CollectionObject {
+        let collection_object = CollectionObject::new(title);
+        for task_object in tasks {
+            collection_object.tasks().append(task_object);
+        }
+        collection_object
+    }
+
+    #[test]
+    fn collections_round_trip() {
+        let task_object = TaskObject::new("Call Mom");
+        task_object.set_checked(true);
+        task_object.set_tags(vec![String::from("phone")]);
+        task_object.set_notes("Ask about the holidays\n\nand the garden");
+        task_object.set_due(DueDate::from_ymd(2025, 3, 15));
+        let subtask = TaskObject::new("Book flights");
+        subtask.set_checked(true);
+        task_object.subtasks().append(&subtask);
+
+        let collections = import(
+            &export(&[collection("Family", std::slice::from_ref(&task_object))]),
+            "Imported",
+        );
+        assert_eq!(collections.len(), 1);
+        assert_eq!(collections[0].title(), "Family");
+        let parsed = collections[0]
+            .tasks()
+            .item(0)
+            .and_downcast::<TaskObject>()
+            .unwrap();
+        assert_eq!(parsed.name(), "Call Mom");
+        assert!(parsed.checked());
+        assert_eq!(parsed.tags(), task_object.tags());
+        assert_eq!(parsed.notes(), task_object.notes());
+        assert_eq!(parsed.due(), task_object.due());
+        let subtask = parsed
+            .subtasks()
+            .item(0)
+            .and_downcast::<TaskObject>()
+            .unwrap();
+        assert_eq!(subtask.name(), "Book flights");
+    }
+
+    #[test]
+    fn names_ending_like_tags_keep_them() {
+        let task_object = TaskObject::new("Read ch. 3 :draft:");
+        let tagged = TaskObject::new("Read ch. 3 :draft:");
+        tagged.set_tags(vec![String::from("school")]);
+
+        let collections = import(
+            &export(&[collection("Books", &[task_object, tagged])]),
+            "Imported",
+        );
+        for (position, tags) in [(0, vec![]), (1, vec![String::from("school")])] {
+            let parsed = collections[0]
+                .tasks()
+                .item(position)
+                .and_downcast::<TaskObject>()
+                .unwrap();
+            assert_eq!(parsed.name(), "Read ch. 3 :draft:");
+            assert_eq!(parsed.tags(), tags);
+        }
+    }
+
+    #[test]
+    fn titles_looking_like_tasks_stay_collections() {
+        for title in ["TODO list", "DONE and dusted", "Errands :home:"] {
+            let collections = import(&export(&[collection(title, &[])]), "Imported");
+            assert_eq!(collections.len(), 1);
+            assert_eq!(collections[0].title(), title);
+            assert_eq!(collections[0].tasks().n_items(), 0);
+        }
+    }
+
+    #[test]
+    fn priorities_use_the_default_range() {
+        for (priority, expected) in [
+            (Priority::Urgent, "[#A]"),
+            (Priority::High, "[#A]"),
+            (Priority::Medium, "[#B]"),
+            (Priority::Low, "[#C]"),
+        ] {
+            let task_object = TaskObject::new("Call Mom");
+            task_object.set_priority(priority);
+            let text = export(&[collection("Family", &[task_object])]);
+            assert!(text.contains(&format!("** TODO {expected} Call Mom")));
+        }
+        let collections = import("* TODO [#C] Call Mom", "Imported");
+        let parsed = collections[0]
+            .tasks()
+            .item(0)
+            .and_downcast::<TaskObject>()
+            .unwrap();
+        assert_eq!(parsed.priority(), Priority::Low);
+    }
+}
@@ -0,0 +1,255 @@
+use gtk::glib::{DateTime, TimeZone};
+use serde_json::{Map, Value, json};
+
+use super::{Error, flatten};
+use crate::{
+    collection_object::CollectionObject, due_date::DueDate, priority::Priority,
+    task_object::TaskObject,
+};
+
+/// Collections as the JSON array written by `task export` and read by `task import` of
+/// [Taskwarrior](https://taskwarrior.org/docs/design/task/)
+///
+/// The collection of a task is its `project`. Taskwarrior has no nesting, so subtasks
+/// follow their parent task as tasks of their own.
+pub fn export(collections: &[CollectionObject]) -> String {
+    let tasks: Vec<Value> = collections
+        .iter()
+        .flat_map(|collection_object| {
+            let project = collection_object.title();
+            flatten(&collection_object.tasks())
+                .into_iter()
+                .map(move |task_object| task(&task_object, &project))
+        })
+        .collect();
+
+    serde_json::to_string_pretty(&tasks).unwrap()
+}
+
+fn task(task_object: &TaskObject, project: &str) -> Value {
+    let mut task = Map::new();
+    task.insert("uuid".into(), json!(task_object.get_id()));
+    task.insert("description".into(), json!(task_object.name()));
+    task.insert(
+        "status".into(),
+        json!(if task_object.checked() {
+            "completed"
+        } else {
+            "pending"
+        }),
+    );
+    // Taskwarrior requires the creation date
+    let entry = task_object
+        .created_at()
+        .or_else(|| DateTime::now_utc().ok());
+    task.insert("entry".into(), json!(entry.as_ref().map(format_date_time)));
+    if let Some(modified) = task_object.modified_at() {
+        task.insert("modified".into(), json!(format_date_time(&modified)));
+    }
+    if task_object.checked()
+        && let Some(end) = task_object.completed_at().or(task_object.modified_at())
+    {
+        task.insert("end".into(), json!(format_date_time(&end)));
+    }
+    if !project.is_empty() {
+        task.insert("project".into(), json!(project));
+    }
+    if let Some(due) = task_object.due() {
+        task.insert("due".into(), json!(format_date_time(due.date_time())));
+    }
+    if let Some(priority) = letter(task_object.priority()) {
+        task.insert("priority".into(), json!(priority));
+    }
+    if !task_object.tags().is_empty() {
+        task.insert("tags".into(), json!(task_object.tags()));
+    }
+    if !task_object.notes().is_empty() {
+        task.insert(
+            "annotations".into(),
+            json!([{
+                "entry": entry.as_ref().map(format_date_time),
+                "description": task_object.notes(),
+            }]),
+        );
+    }
+
+    Value::Object(task)
+}
+
+/// Read the collections from Taskwarrior JSON, see `export`
+///
+/// Deleted tasks and the templates of recurring tasks are skipped.
+pub fn import(text: &str, default_title: &str) -> Result<Vec<CollectionObject>, Error> {
+    let tasks: Vec<Map<String, Value>> = serde_json::from_str(text)?;
+    let mut collections: Vec<CollectionObject> = Vec::new();
+
+    for task in &tasks {
+        let Some(task_object) = parse_task(task) else {
+            continue;
+        };
+        let title = task
+            .get("project")
+            .and_then(Value::as_str)
+            .filter(|p| !p.is_empty())
+            .unwrap_or(default_title);
+
+        let collection_object = match collections.iter().find(|c| c.title() == title) {
+            Some(c) => c.clone(),
+            None => {
+                let c = CollectionObject::new(title);
+                collections.push(c.clone());
+                c
+            }
+        };
+        collection_object.tasks().append(&task_object);
+    }
+
+    Ok(collections)
+}
+
+fn parse_task(task: &Map<String, Value>) -> Option<TaskObject> {
+    let get = |key: &str| task.get(key).and_then(Value::as_str);
+    let date_time = |key: &str| get(key).and_then(parse_date_time);
+
+    let checked = match get("status")? {
+        "pending" | "waiting" => false,
+        "completed" => true,
+        _ => return None,
+    };
+    let name = get("description").filter(|d| !d.trim().is_empty())?;
+
+    let task_object = match get("uuid") {
+        Some(uuid) if !uuid.is_empty() => TaskObject::with_id(name, uuid.to_string()),
+        _ => TaskObject::new(name),
+    };
+    task_object.set_checked(checked);
+    task_object.set_due(date_time("due").map(due_date));
+    task_object.set_priority(get("priority").map_or(Priority::None, parse_letter));
+    if let Some(tags) = task.get("tags").and_then(Value::as_array) {
+        task_object.set_tags(
+            tags.iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect::<Vec<_>>(),
+        );
+    }
+    if let Some(annotations) = task.get("annotations").and_then(Value::as_array) {
+        let notes: Vec<&str> = annotations
+            .iter()
+            .filter_map(|a| a.get("description")?.as_str())
+            .collect();
+        task_object.set_notes(notes.join("\n"));
+    }
+    // Restore the dates last, as setting the fields above touches the timestamps
+    if let Some(entry) = date_time("entry") {
+        task_object.set_created_at(Some(entry));
+    }
+    if let Some(modified) = date_time("modified") {
+        task_object.set_modified_at(Some(modified));
+    }
+    if checked && let Some(end) = date_time("end") {
+        task_object.set_completed_at(Some(end));
+    }
+
+    Some(task_object)
+}
+
+/// Format a point in time as Taskwarrior does, e.g. `20250314T173000Z`
+fn format_date_time(date_time: &DateTime) -> String {
+    date_time
+        .to_utc()
+        .and_then(|d| d.format("%Y%m%dT%H%M%SZ"))
+        .map(String::from)
+        .unwrap_or_default()
+}
+
+fn parse_date_time(text: &str) -> Option<DateTime> {
+    DateTime::from_iso8601(text, Some(&TimeZone::utc())).ok()
+}
+
+/// Taskwarrior has no days without a time, a due date at midnight is taken as a day
+fn due_date(date_time: DateTime) -> DueDate {
+    match date_time.to_local() {
+        Ok(local) if (local.hour(), local.minute(), local.second()) == (0, 0, 0) => {
+            let (year, month, day) = local.ymd();
+            DueDate::from_ymd(year, month, day).unwrap_or_else(|| DueDate::from_date_time(local))
+        }
+        _ => DueDate::from_date_time(date_time),
+    }
+}
+
+/// Letter of a priority, Taskwarrior only has three of them
+fn letter(priority: Priority) -> Option<&'static str> {
+    match priority {
+        Priority::None => None,
+        Priority::Low => Some("L"),
+        Priority::Medium => Some("M"),
+        Priority::High | Priority::Urgent => Some("H"),
+    }
+}
+
+fn parse_letter(text: &str) -> Priority {
+    match text {
+        "H" => Priority::High,
+        "M" => Priority::Medium,
+        "L" => Priority::Low,
+        _ => Priority::None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gtk::gio::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn projects_round_trip() {
+        let collection_object = CollectionObject::new("Family");
+        let task_object = TaskObject::new("Call Mom");
+        task_object.set_tags(vec![String::from("phone")]);
+        task_object.set_notes("Ask about the holidays");
+        task_object.set_priority(Priority::Medium);
+        task_object.set_due(DueDate::from_ymd(2025, 3, 15));
+        let subtask = TaskObject::new("Book flights");
+        subtask.set_checked(true);
+        task_object.subtasks().append(&subtask);
+        collection_object.tasks().append(&task_object);
+
+        let collections = import(&export(&[collection_object]), "Imported").unwrap();
+        assert_eq!(collections.len(), 1);
+        assert_eq!(collections[0].title(), "Family");
+        // Subtasks become tasks of their own
+        assert_eq!(collections[0].tasks().n_items(), 2);
+        let parsed = collections[0]
+            .tasks()
+            .item(0)
+            .and_downcast::<TaskObject>()
+            .unwrap();
+        assert_eq!(parsed.get_id(), task_object.get_id());
+        assert_eq!(parsed.name(), "Call Mom");
+        assert_eq!(parsed.tags(), task_object.tags());
+        assert_eq!(parsed.notes(), task_object.notes());
+        assert_eq!(parsed.priority(), Priority::Medium);
+        assert_eq!(parsed.due(), task_object.due());
+        let parsed = collections[0]
+            .tasks()
+            .item(1)
+            .and_downcast::<TaskObject>()
+            .unwrap();
+        assert!(parsed.checked());
+    }
+
+    #[test]
+    fn deleted_tasks_and_templates_are_skipped() {
+        let text = r#"[
+            {"uuid": "a", "description": "Call Mom", "status": "deleted"},
+            {"uuid": "b", "description": "Water plants", "status": "recurring"},
+            {"uuid": "c", "description": "Buy milk", "status": "waiting"}
+        ]"#;
+        let collections = import(text, "Imported").unwrap();
+        assert_eq!(collections.len(), 1);
+        assert_eq!(collections[0].title(), "Imported");
+        assert_eq!(collections[0].tasks().n_items(), 1);
+    }
+}
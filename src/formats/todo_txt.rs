@@ -0,0 +1,301 @@
+use gtk::glib::DateTime;
+
+use super::{flatten, format_date, parse_date};
+use crate::{
+    collection_object::CollectionObject,
+    due_date::DueDate,
+    priority::Priority,
+    task_object::{self, TaskObject},
+};
+
+/// Collections as [todo.txt](https://github.com/todotxt/todo.txt), a line per task such as
+/// `x 2025-03-14 2025-03-01 +Family Call Mom @phone due:2025-03-15 pri:B`
+///
+/// The collection of a task is its first `+project`, written before the name, and tags
+/// are `@contexts`. As todo.txt has no nesting, subtasks follow their parent task as
+/// tasks of their own.
+///
+/// todo.txt has no escaping, so this adds its own: words of a name that would be read
+/// as something else, such as `+1` or a leading `x`, are prefixed with a backslash.
+pub fn export(collections: &[CollectionObject]) -> String {
+    let mut text = String::new();
+    for collection_object in collections {
+        let project = project_name(&collection_object.title());
+        for task_object in flatten(&collection_object.tasks()) {
+            text.push_str(&line(&task_object, &project));
+            text.push('\n');
+        }
+    }
+
+    text
+}
+
+fn line(task_object: &TaskObject, project: &str) -> String {
+    let mut words: Vec<String> = Vec::new();
+    let created = task_object.created_at().as_ref().map(format_date);
+    let priority = letter(task_object.priority());
+
+    if task_object.checked() {
+        words.push(String::from("x"));
+        // A creation date may only be given together with the completion date
+        if let Some(completed) = task_object.completed_at() {
+            words.push(format_date(&completed));
+            words.extend(created);
+        }
+    } else {
+        words.extend(priority.map(|p| format!("({p})")));
+        words.extend(created);
+    }
+
+    // The project comes first, so that it isn't confused with a `+word` of the name
+    if !project.is_empty() {
+        words.push(format!("+{project}"));
+    }
+    words.extend(task_object.name().split_whitespace().map(escape));
+    words.extend(task_object.tags().iter().map(|tag| format!("@{tag}")));
+    if let Some(due) = task_object.due() {
+        let (year, month, day) = due.ymd();
+        words.push(format!("due:{year:04}-{month:02}-{day:02}"));
+    }
+    // Completed tasks keep their priority as a tag, as the marker has to come first
+    if task_object.checked()
+        && let Some(p) = priority
+    {
+        words.push(format!("pri:{p}"));
+    }
+
+    words.join(" ")
+}
+
+/// Read the collections from todo.txt, see `export`
+pub fn import(text: &str, default_title: &str) -> Vec<CollectionObject> {
+    let mut collections: Vec<CollectionObject> = Vec::new();
+
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let (project, task_object) = parse_line(line);
+        let title = project.map_or_else(|| default_title.to_string(), |p| parse_project(&p));
+
+        let collection_object = match collections.iter().find(|c| c.title() == title) {
+            Some(c) => c.clone(),
+            None => {
+                let c = CollectionObject::new(&title);
+                collections.push(c.clone());
+                c
+            }
+        };
+        collection_object.tasks().append(&task_object);
+    }
+
+    collections
+}
+
+/// Parse a line into the first project it mentions and the task it describes
+fn parse_line(line: &str) -> (Option<String>, TaskObject) {
+    let mut words = line.split_whitespace().peekable();
+
+    let checked = words.next_if_eq(&"x").is_some();
+    let mut priority = match words.peek().and_then(|w| parse_priority_marker(w)) {
+        Some(p) if !checked => {
+            words.next();
+            p
+        }
+        _ => Priority::None,
+    };
+    let mut dates = Vec::new();
+    // Completed tasks may have a completion date followed by a creation date
+    while dates.len() < if checked { 2 } else { 1 }
+        && let Some(date) = words.peek().and_then(|w| parse_date(w))
+    {
+        words.next();
+        dates.push(date);
+    }
+    let (completed, created): (Option<DateTime>, Option<DateTime>) = if checked {
+        (dates.first().cloned(), dates.get(1).cloned())
+    } else {
+        (None, dates.first().cloned())
+    };
+
+    let mut project = None;
+    let mut tags = Vec::new();
+    let mut due = None;
+    let mut name = Vec::new();
+    for word in words {
+        if let Some(word) = word.strip_prefix('\\') {
+            name.push(word);
+        } else if project.is_none()
+            && let Some(p) = word.strip_prefix('+').filter(|p| !p.is_empty())
+        {
+            project = Some(p.to_string());
+        } else if let Some(tag) = word.strip_prefix('@').filter(|t| !t.is_empty()) {
+            tags.push(tag);
+        } else if let Some(d) = word.strip_prefix("due:").and_then(DueDate::parse) {
+            due = Some(d);
+        } else if let Some(p) = word.strip_prefix("pri:").and_then(parse_letter) {
+            priority = p;
+        } else {
+            name.push(word);
+        }
+    }
+    let name = if name.is_empty() {
+        line.to_string()
+    } else {
+        name.join(" ")
+    };
+
+    let task_object = TaskObject::new(&name);
+    task_object.set_checked(checked);
+    task_object.set_priority(priority);
+    task_object.set_tags(task_object::parse_tags(&tags.join(" ")));
+    task_object.set_due(due);
+    // Restore the dates last, as setting the fields above touches the timestamps
+    if created.is_some() {
+        task_object.set_created_at(created.clone());
+    }
+    if completed.is_some() {
+        task_object.set_completed_at(completed.clone());
+    }
+    if let Some(modified) = completed.or(created) {
+        task_object.set_modified_at(Some(modified));
+    }
+
+    (project, task_object)
+}
+
+/// Project naming a collection, a single word with the spaces of `title` written as
+/// underscores, and underscores and backslashes escaped with a backslash
+fn project_name(title: &str) -> String {
+    title
+        .split_whitespace()
+        .map(|word| word.replace('\\', "\\\\").replace('_', "\\_"))
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Title of the collection named by a project, see `project_name`
+fn parse_project(project: &str) -> String {
+    let mut title = String::new();
+    let mut chars = project.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => title.extend(chars.next()),
+            '_' => title.push(' '),
+            _ => title.push(c),
+        }
+    }
+
+    title
+}
+
+/// Prefix a word of a name with a backslash if it would be read as something else
+fn escape(word: &str) -> String {
+    let special = word.starts_with(['+', '@', '\\'])
+        || word.starts_with("due:")
+        || word.starts_with("pri:")
+        || word == "x"
+        || parse_priority_marker(word).is_some()
+        || parse_date(word).is_some();
+
+    if special {
+        format!("\\{word}")
+    } else {
+        word.to_string()
+    }
+}
+
+/// Letter of a priority, todo.txt has 26 where `A` is the most important
+fn letter(priority: Priority) -> Option<char> {
+    match priority {
+        Priority::None => None,
+        Priority::Low => Some('D'),
+        Priority::Medium => Some('C'),
+        Priority::High => Some('B'),
+        Priority::Urgent => Some('A'),
+    }
+}
+
+/// Parse a priority letter, letters after `D` are low priorities as well
+fn parse_letter(text: &str) -> Option<Priority> {
+    match text {
+        "A" => Some(Priority::Urgent),
+        "B" => Some(Priority::High),
+        "C" => Some(Priority::Medium),
+        _ if text.len() == 1 && text.chars().all(|c| c.is_ascii_uppercase()) => Some(Priority::Low),
+        _ => None,
+    }
+}
+
+/// Parse a priority marker such as `(A)`
+fn parse_priority_marker(word: &str) -> Option<Priority> {
+    parse_letter(word.strip_prefix('(')?.strip_suffix(')')?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Export a task named `name` in a collection titled `title` and read it back
+    fn round_trip(name: &str, title: &str) -> (Option<String>, TaskObject) {
+        let (project, task_object) =
+            parse_line(&line(&TaskObject::new(name), &project_name(title)));
+        (project.map(|p| parse_project(&p)), task_object)
+    }
+
+    #[test]
+    fn names_keep_words_looking_like_markup() {
+        for name in [
+            "Vote +1 on PR",
+            "x marks the spot",
+            "(A) is the answer",
+            "2025-03-14 retrospective",
+            "Mail @x about due:friday and pri:A",
+            "Escape \\n in strings",
+        ] {
+            let (project, task_object) = round_trip(name, "Work");
+            assert_eq!(project.as_deref(), Some("Work"));
+            assert_eq!(task_object.name(), name);
+        }
+    }
+
+    #[test]
+    fn names_without_project_keep_leading_markers() {
+        let (project, task_object) = round_trip("x marks the spot", "");
+        assert_eq!(project, None);
+        assert_eq!(task_object.name(), "x marks the spot");
+        assert!(!task_object.checked());
+    }
+
+    #[test]
+    fn titles_keep_underscores() {
+        for title in ["Work", "my_list", "Home and garden", "back\\slash _"] {
+            assert_eq!(round_trip("Call Mom", title).0.as_deref(), Some(title));
+        }
+    }
+
+    #[test]
+    fn fields_survive() {
+        let task_object = TaskObject::new("Call Mom");
+        task_object.set_checked(true);
+        task_object.set_priority(Priority::High);
+        task_object.set_tags(vec![String::from("family"), String::from("phone")]);
+        task_object.set_due(DueDate::from_ymd(2025, 3, 15));
+
+        let (project, parsed) = parse_line(&line(&task_object, "Family"));
+        assert_eq!(project.as_deref(), Some("Family"));
+        assert_eq!(parsed.name(), "Call Mom");
+        assert!(parsed.checked());
+        assert_eq!(parsed.priority(), Priority::High);
+        assert_eq!(parsed.tags(), task_object.tags());
+        assert_eq!(parsed.due(), task_object.due());
+    }
+
+    #[test]
+    fn reads_lines_of_other_apps() {
+        let (project, task_object) =
+            parse_line("(B) 2025-03-01 Call Mom +Family @phone due:2025-03-15");
+        assert_eq!(project.as_deref(), Some("Family"));
+        assert_eq!(task_object.name(), "Call Mom");
+        assert_eq!(task_object.priority(), Priority::High);
+        assert_eq!(task_object.tags(), vec![String::from("phone")]);
+        assert_eq!(task_object.due(), DueDate::from_ymd(2025, 3, 15));
+    }
+}
@@ -0,0 +1,188 @@
+use std::cell::RefCell;
+
+use gtk::{
+    gio::{ListStore, prelude::*},
+    glib::{Object, Value},
+};
+
+use crate::{collection_object::CollectionObject, task_object::TaskObject};
+
+/// Number of commands kept for undoing, older ones are dropped
+const MAX_LENGTH: usize = 100;
+
+/// Change of the application's state which has already been applied and can be reverted
+pub enum Command {
+    InsertTask {
+        tasks: ListStore,
+        position: u32,
+        task_object: TaskObject,
+    },
+    RemoveTask {
+        tasks: ListStore,
+        position: u32,
+        task_object: TaskObject,
+    },
+    /// Move the task at `from` so that it ends up at `to`
+    MoveTask {
+        tasks: ListStore,
+        from: u32,
+        to: u32,
+    },
+    InsertCollection {
+        collections: ListStore,
+        position: u32,
+        collection_object: CollectionObject,
+    },
+    RemoveCollection {
+        collections: ListStore,
+        position: u32,
+        collection_object: CollectionObject,
+    },
+    SetProperty {
+        object: Object,
+        property: &'static str,
+        old: Value,
+        new: Value,
+    },
+    /// Commands applied in order, reverted in reverse order
+    Group(Vec<Command>),
+}
+
+impl Command {
+    /// Record the change of `property` to `new`, remembering the current value
+    pub fn set_property(object: &impl IsA<Object>, property: &'static str, new: Value) -> Self {
+        Command::SetProperty {
+            object: object.clone().upcast(),
+            property,
+            old: object.property_value(property),
+            new,
+        }
+    }
+
+    pub fn apply(&self) {
+        match self {
+            Command::InsertTask {
+                tasks,
+                position,
+                task_object,
+            } => tasks.insert(*position, task_object),
+            Command::RemoveTask {
+                tasks, position, ..
+            } => tasks.remove(*position),
+            Command::MoveTask { tasks, from, to } => move_item(tasks, *from, *to),
+            Command::InsertCollection {
+                collections,
+                position,
+                collection_object,
+            } => collections.insert(*position, collection_object),
+            Command::RemoveCollection {
+                collections,
+                position,
+                ..
+            } => collections.remove(*position),
+            Command::SetProperty {
+                object,
+                property,
+                new,
+                ..
+            } => object.set_property_from_value(property, new),
+            Command::Group(commands) => commands.iter().for_each(Command::apply),
+        }
+    }
+
+    pub fn revert(&self) {
+        match self {
+            Command::InsertTask {
+                tasks, position, ..
+            } => tasks.remove(*position),
+            Command::RemoveTask {
+                tasks,
+                position,
+                task_object,
+            } => tasks.insert(*position, task_object),
+            Command::MoveTask { tasks, from, to } => move_item(tasks, *to, *from),
+            Command::InsertCollection {
+                collections,
+                position,
+                ..
+            } => collections.remove(*position),
+            Command::RemoveCollection {
+                collections,
+                position,
+                collection_object,
+            } => collections.insert(*position, collection_object),
+            Command::SetProperty {
+                object,
+                property,
+                old,
+                ..
+            } => object.set_property_from_value(property, old),
+            Command::Group(commands) => commands.iter().rev().for_each(Command::revert),
+        }
+    }
+}
+
+/// Move the item at `from` of `list` to `to`, keeping the same object
+fn move_item(list: &ListStore, from: u32, to: u32) {
+    let item = list.item(from).unwrap();
+    list.remove(from);
+    list.insert(to, &item);
+}
+
+/// Undo and redo stacks of applied commands
+///
+/// The stacks are not borrowed while a command runs, so that the changes it causes
+/// may record further commands.
+#[derive(Default)]
+pub struct History {
+    undo_stack: RefCell<Vec<Command>>,
+    redo_stack: RefCell<Vec<Command>>,
+}
+
+impl History {
+    /// Record a command which has just been applied
+    pub fn push(&self, command: Command) {
+        let mut undo_stack = self.undo_stack.borrow_mut();
+        undo_stack.push(command);
+        if undo_stack.len() > MAX_LENGTH {
+            undo_stack.remove(0);
+        }
+        self.redo_stack.borrow_mut().clear();
+    }
+
+    /// Revert the last command, returns whether there was one
+    pub fn undo(&self) -> bool {
+        let Some(command) = self.undo_stack.borrow_mut().pop() else {
+            return false;
+        };
+        command.revert();
+        self.redo_stack.borrow_mut().push(command);
+
+        true
+    }
+
+    /// Apply the last reverted command again, returns whether there was one
+    pub fn redo(&self) -> bool {
+        let Some(command) = self.redo_stack.borrow_mut().pop() else {
+            return false;
+        };
+        command.apply();
+        self.undo_stack.borrow_mut().push(command);
+
+        true
+    }
+
+    /// Forget all commands, e.g. after the objects they refer to changed otherwise
+    pub fn clear(&self) {
+        self.undo_stack.borrow_mut().clear();
+        self.redo_stack.borrow_mut().clear();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.borrow().is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.borrow().is_empty()
+    }
+}
@@ -1,13 +1,21 @@
 use std::path::PathBuf;
 
 use adw::prelude::*;
+use application::Application;
 use gtk::gio;
 use gtk::glib;
-use main_window::MainWindow;
 
 const APP_ID: &str = "me.gtk-rs-test.test";
 
+mod application;
+mod autosave;
 mod collection_object;
+mod due_date;
+mod formats;
+mod history;
+mod priority;
+mod recurrence;
+mod storage;
 mod task_object;
 
 mod main_window;
@@ -23,11 +31,5 @@ fn data_path() -> PathBuf {
 fn main() -> glib::ExitCode {
     gio::resources_register_include!("compiled.gresource").expect("Failed to register gresources");
 
-    let app = adw::Application::builder().application_id(APP_ID).build();
-    app.connect_activate(activate);
-    app.run()
-}
-
-fn activate(app: &adw::Application) {
-    MainWindow::builder().application(&app).build().present();
+    Application::new().run()
 }
@@ -6,7 +6,10 @@ use gtk::{Widget, Window, glib};
 
 use gtk::gio::{self, ActionEntry};
 
-use crate::{collection_object, task_object};
+use crate::{
+    application::Application, collection_object, due_date::DueDate, priority::Priority,
+    recurrence::Recurrence, task_object,
+};
 
 glib::wrapper! {
     pub struct MainWindow(ObjectSubclass<main_window_imp::MainWindowImp>)
@@ -16,8 +19,11 @@ glib::wrapper! {
 
 mod main_window_imp;
 
+mod backup_dialog;
 mod collection_row;
 mod collection_wizard;
+mod csv_import_dialog;
+mod task_detail;
 mod task_row;
 
 impl MainWindow {
@@ -32,22 +38,106 @@ impl MainWindow {
                     window.imp().show_about_dialog();
                 })
                 .build(),
+            ActionEntry::builder("undo")
+                .activate(|window: &Self, _, _| {
+                    window.imp().undo();
+                })
+                .build(),
+            ActionEntry::builder("redo")
+                .activate(|window: &Self, _, _| {
+                    window.imp().redo();
+                })
+                .build(),
             ActionEntry::builder("save")
                 .activate(|window: &Self, _, _| {
                     window.imp().save();
                 })
                 .build(),
+            ActionEntry::builder("show-backups")
+                .activate(|window: &Self, _, _| {
+                    window.imp().show_backups_dialog();
+                })
+                .build(),
+            ActionEntry::builder("restore-backup")
+                .parameter_type(Some(&String::static_variant_type()))
+                .activate(|window: &Self, _, path| {
+                    let path = path.and_then(|v| v.get::<String>()).unwrap();
+                    window.imp().restore_backup(&path);
+                })
+                .build(),
+            ActionEntry::builder("import")
+                .parameter_type(Some(&String::static_variant_type()))
+                .activate(|window: &Self, _, format| {
+                    let format = format.and_then(|v| v.get::<String>()).unwrap();
+                    window.imp().show_import_dialog(format.as_str().into());
+                })
+                .build(),
+            ActionEntry::builder("export")
+                .parameter_type(Some(&String::static_variant_type()))
+                .activate(|window: &Self, _, format| {
+                    let format = format.and_then(|v| v.get::<String>()).unwrap();
+                    window
+                        .imp()
+                        .show_export_dialog(format.as_str().into(), None);
+                })
+                .build(),
+            ActionEntry::builder("export-collection")
+                .parameter_type(Some(
+                    &<(collection_object::IdType, String)>::static_variant_type(),
+                ))
+                .activate(|window: &Self, _, parameter| {
+                    let (id, format) = parameter
+                        .and_then(|t| t.get::<(collection_object::IdType, String)>())
+                        .unwrap();
+                    window
+                        .imp()
+                        .show_export_dialog(format.as_str().into(), Some(id));
+                })
+                .build(),
+            ActionEntry::builder("copy-markdown")
+                .parameter_type(Some(&collection_object::IdType::static_variant_type()))
+                .activate(|window: &Self, _, id| {
+                    let id = id
+                        .and_then(|t| t.get::<collection_object::IdType>())
+                        .unwrap();
+                    window.imp().copy_markdown(id);
+                })
+                .build(),
             ActionEntry::builder("remove-done-tasks")
                 .activate(|window: &Self, _, _| {
                     window.imp().remove_done_tasks();
                 })
                 .build(),
             ActionEntry::builder("add-collection")
-                .parameter_type(Some(&String::static_variant_type()))
-                .activate(|window: &Self, _, title| {
-                    let title = title.and_then(|v| v.get::<String>()).unwrap();
+                .parameter_type(Some(&<(String, String, String)>::static_variant_type()))
+                .activate(|window: &Self, _, parameter| {
+                    let (title, color, icon) = parameter
+                        .and_then(|v| v.get::<(String, String, String)>())
+                        .unwrap();
 
-                    window.imp().add_collection(&title);
+                    window.imp().add_collection(&title, &color, &icon);
+                })
+                .build(),
+            ActionEntry::builder("show-collection-editor")
+                .parameter_type(Some(&collection_object::IdType::static_variant_type()))
+                .activate(|window: &Self, _, id| {
+                    let id = id
+                        .and_then(|t| t.get::<collection_object::IdType>())
+                        .unwrap();
+                    window.imp().show_collection_editor(id);
+                })
+                .build(),
+            ActionEntry::builder("edit-collection")
+                .parameter_type(Some(
+                    &<(collection_object::IdType, String, String, String)>::static_variant_type(),
+                ))
+                .activate(|window: &Self, _, parameter| {
+                    let (id, title, color, icon) = parameter
+                        .and_then(|t| {
+                            t.get::<(collection_object::IdType, String, String, String)>()
+                        })
+                        .unwrap();
+                    window.imp().edit_collection(id, &title, &color, &icon);
                 })
                 .build(),
             ActionEntry::builder("remove-task")
@@ -57,6 +147,129 @@ impl MainWindow {
                     window.imp().remove_task_by_id(id);
                 })
                 .build(),
+            ActionEntry::builder("show-task-details")
+                .parameter_type(Some(&task_object::IdType::static_variant_type()))
+                .activate(|window: &Self, _, id| {
+                    let id = id.and_then(|t| t.get::<task_object::IdType>()).unwrap();
+                    window.imp().show_task_details_by_id(id);
+                })
+                .build(),
+            ActionEntry::builder("rename-task")
+                .parameter_type(Some(&<(task_object::IdType, String)>::static_variant_type()))
+                .activate(|window: &Self, _, parameter| {
+                    let (id, name) = parameter
+                        .and_then(|t| t.get::<(task_object::IdType, String)>())
+                        .unwrap();
+                    window.imp().rename_task(id, &name);
+                })
+                .build(),
+            ActionEntry::builder("move-task")
+                .parameter_type(Some(
+                    &<(task_object::IdType, task_object::IdType, bool)>::static_variant_type(),
+                ))
+                .activate(|window: &Self, _, parameter| {
+                    let (id, target, after) = parameter
+                        .and_then(|t| t.get::<(task_object::IdType, task_object::IdType, bool)>())
+                        .unwrap();
+                    window.imp().move_task(id, target, after);
+                })
+                .build(),
+            ActionEntry::builder("check-task")
+                .parameter_type(Some(&<(task_object::IdType, bool)>::static_variant_type()))
+                .activate(|window: &Self, _, parameter| {
+                    let (id, checked) = parameter
+                        .and_then(|t| t.get::<(task_object::IdType, bool)>())
+                        .unwrap();
+                    window.imp().check_task(id, checked);
+                })
+                .build(),
+            ActionEntry::builder("add-subtask")
+                .parameter_type(Some(&<(task_object::IdType, String)>::static_variant_type()))
+                .activate(|window: &Self, _, parameter| {
+                    let (id, name) = parameter
+                        .and_then(|t| t.get::<(task_object::IdType, String)>())
+                        .unwrap();
+                    window.imp().add_subtask(id, &name);
+                })
+                .build(),
+            ActionEntry::builder("set-task-priority")
+                .parameter_type(Some(&<(task_object::IdType, String)>::static_variant_type()))
+                .activate(|window: &Self, _, parameter| {
+                    let (id, priority) = parameter
+                        .and_then(|t| t.get::<(task_object::IdType, String)>())
+                        .unwrap();
+                    window.imp().set_task_property(
+                        id,
+                        "priority",
+                        Priority::from(priority.as_str()),
+                    );
+                })
+                .build(),
+            // The due date in the format of the data file, or empty to clear it
+            ActionEntry::builder("set-task-due")
+                .parameter_type(Some(&<(task_object::IdType, String)>::static_variant_type()))
+                .activate(|window: &Self, _, parameter| {
+                    let (id, due) = parameter
+                        .and_then(|t| t.get::<(task_object::IdType, String)>())
+                        .unwrap();
+                    let due = match due.as_str() {
+                        "" => None,
+                        due => match DueDate::parse(due) {
+                            Some(due) => Some(due),
+                            None => return,
+                        },
+                    };
+                    window.imp().set_task_property(id, "due", due);
+                })
+                .build(),
+            // The recurrence as JSON, or empty to clear it
+            ActionEntry::builder("set-task-recurrence")
+                .parameter_type(Some(&<(task_object::IdType, String)>::static_variant_type()))
+                .activate(|window: &Self, _, parameter| {
+                    let (id, recurrence) = parameter
+                        .and_then(|t| t.get::<(task_object::IdType, String)>())
+                        .unwrap();
+                    let recurrence = match recurrence.as_str() {
+                        "" => None,
+                        recurrence => match serde_json::from_str::<Recurrence>(recurrence) {
+                            Ok(recurrence) => Some(recurrence),
+                            Err(_) => return,
+                        },
+                    };
+                    window.imp().set_task_property(id, "recurrence", recurrence);
+                })
+                .build(),
+            ActionEntry::builder("set-task-tags")
+                .parameter_type(Some(
+                    &<(task_object::IdType, Vec<String>)>::static_variant_type(),
+                ))
+                .activate(|window: &Self, _, parameter| {
+                    let (id, tags) = parameter
+                        .and_then(|t| t.get::<(task_object::IdType, Vec<String>)>())
+                        .unwrap();
+                    window.imp().set_task_property(id, "tags", tags);
+                })
+                .build(),
+            ActionEntry::builder("set-task-notes")
+                .parameter_type(Some(&<(task_object::IdType, String)>::static_variant_type()))
+                .activate(|window: &Self, _, parameter| {
+                    let (id, notes) = parameter
+                        .and_then(|t| t.get::<(task_object::IdType, String)>())
+                        .unwrap();
+                    window.imp().set_task_property(id, "notes", notes);
+                })
+                .build(),
+            ActionEntry::builder("set-task-derive-checked")
+                .parameter_type(Some(&<(task_object::IdType, bool)>::static_variant_type()))
+                .activate(|window: &Self, _, parameter| {
+                    let (id, derive_checked) = parameter
+                        .and_then(|t| t.get::<(task_object::IdType, bool)>())
+                        .unwrap();
+                    window
+                        .imp()
+                        .set_task_property(id, "derive-checked", derive_checked);
+                })
+                .build(),
             ActionEntry::builder("select-collection")
                 .parameter_type(Some(&collection_object::IdType::static_variant_type()))
                 .activate(|window: &Self, _, id| {
@@ -84,13 +297,15 @@ impl MainWindow {
                 .build(),
         ]);
         self.add_action(&PropertyAction::new("filter-mode", self, "filter_mode"));
+        self.add_action(&PropertyAction::new("sort-mode", self, "sort_mode"));
     }
 }
 
 mod builder {
+    use adw::subclass::prelude::ObjectSubclassIsExt;
     use gtk::glib::{Object, object::ObjectBuilder};
 
-    use super::MainWindow;
+    use super::{Application, MainWindow};
 
     pub struct MainWindowBuilder<'a> {
         builder: ObjectBuilder<'a, MainWindow>,
@@ -102,13 +317,15 @@ mod builder {
                 builder: Object::builder(),
             }
         }
-        pub fn application(self, application: &adw::Application) -> Self {
+        pub fn application(self, application: &Application) -> Self {
             Self {
                 builder: self.builder.property("application", application),
             }
         }
         pub fn build(self) -> MainWindow {
-            self.builder.build()
+            let window = self.builder.build();
+            window.imp().setup_application();
+            window
         }
     }
 }
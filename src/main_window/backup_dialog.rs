@@ -0,0 +1,16 @@
+use adw::Dialog;
+use gtk::glib::{self, Object};
+
+glib::wrapper! {
+    pub struct BackupDialog(ObjectSubclass<backup_dialog_imp::BackupDialogImp>)
+        @extends Dialog, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::ShortcutManager;
+}
+
+impl BackupDialog {
+    pub fn new() -> Self {
+        Object::builder().build()
+    }
+}
+
+mod backup_dialog_imp;
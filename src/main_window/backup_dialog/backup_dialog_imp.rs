@@ -0,0 +1,71 @@
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use adw::{ActionRow, Dialog, PreferencesGroup};
+use glib::subclass::InitializingObject;
+use gtk::Button;
+use gtk::CompositeTemplate;
+use gtk::glib;
+
+use crate::storage;
+
+#[derive(CompositeTemplate, Default)]
+#[template(resource = "/me/gtk-rs-test/test/backup_dialog.ui")]
+pub struct BackupDialogImp {
+    #[template_child]
+    backups_group: TemplateChild<PreferencesGroup>,
+}
+
+impl BackupDialogImp {
+    /// Add a row with a restore button for every backup
+    fn fill(&self) {
+        let backups = storage::backups();
+        if backups.is_empty() {
+            self.backups_group
+                .set_description(Some("No backups have been made yet"));
+        }
+
+        for backup in backups {
+            let button = Button::builder()
+                .label("Restore")
+                .valign(gtk::Align::Center)
+                .action_name("win.restore-backup")
+                .action_target(&backup.path().to_string_lossy().to_variant())
+                .build();
+            let dialog = self.obj().downgrade();
+            button.connect_clicked(move |_| {
+                if let Some(dialog) = dialog.upgrade() {
+                    dialog.close();
+                }
+            });
+
+            let row = ActionRow::builder().title(backup.label()).build();
+            row.add_suffix(&button);
+            self.backups_group.add(&row);
+        }
+    }
+}
+
+#[glib::object_subclass]
+impl ObjectSubclass for BackupDialogImp {
+    const NAME: &'static str = "GtkRsTestBackupDialog";
+    type Type = super::BackupDialog;
+    type ParentType = Dialog;
+
+    fn class_init(klass: &mut Self::Class) {
+        klass.bind_template();
+    }
+
+    fn instance_init(obj: &InitializingObject<Self>) {
+        obj.init_template();
+    }
+}
+
+impl ObjectImpl for BackupDialogImp {
+    fn constructed(&self) {
+        self.parent_constructed();
+
+        self.fill();
+    }
+}
+impl WidgetImpl for BackupDialogImp {}
+impl AdwDialogImpl for BackupDialogImp {}
@@ -1,3 +1,5 @@
+use std::cell::RefCell;
+
 use adw::ActionRow;
 use adw::prelude::*;
 use adw::subclass::prelude::*;
@@ -5,32 +7,78 @@ use glib::subclass::InitializingObject;
 use gtk::Button;
 use gtk::CompositeTemplate;
 use gtk::GestureClick;
+use gtk::Image;
 use gtk::PopoverMenu;
 use gtk::gdk::BUTTON_SECONDARY;
 use gtk::gdk::Rectangle;
 use gtk::gio::Menu;
 use gtk::gio::MenuItem;
 use gtk::glib;
+use gtk::glib::Binding;
+use gtk::glib::SignalHandlerId;
 
-use crate::collection_object::CollectionObject;
+use crate::{collection_object::CollectionObject, formats::Format};
 
 #[derive(CompositeTemplate, Default)]
 #[template(resource = "/me/gtk-rs-test/test/collection_row.ui")]
 pub struct CollectionRowImp {
     #[template_child]
     pub(super) suffix_button: TemplateChild<Button>,
+    #[template_child]
+    icon_image: TemplateChild<Image>,
 
     #[template_child]
     pub(super) rightclick_menu_model: TemplateChild<Menu>,
     #[template_child]
     rightclick_menu: TemplateChild<PopoverMenu>,
+
+    /// Collection shown by the row, with the binding of its title and the handlers
+    /// updating the icon, released when the row is disposed
+    collection_object: RefCell<Option<CollectionObject>>,
+    title_binding: RefCell<Option<Binding>>,
+    handlers: RefCell<Vec<SignalHandlerId>>,
 }
 
 impl CollectionRowImp {
     pub(super) fn associate(&self, collection_object: &CollectionObject) {
         self.suffix_button
             .set_action_target(Some(collection_object.get_id().to_variant()));
-        self.obj().set_title(&collection_object.title());
+        self.release();
+        self.collection_object
+            .replace(Some(collection_object.clone()));
+        self.title_binding.replace(Some(
+            collection_object
+                .bind_property("title", &*self.obj(), "title")
+                .sync_create()
+                .build(),
+        ));
+        self.update_icon(collection_object);
+        for property in ["color", "icon"] {
+            let row = self.downgrade();
+            let handler = collection_object.connect_notify_local(
+                Some(property),
+                move |collection_object, _| {
+                    if let Some(row) = row.upgrade() {
+                        row.update_icon(collection_object);
+                    }
+                },
+            );
+            self.handlers.borrow_mut().push(handler);
+        }
+
+        let menu_item = MenuItem::new(Some("Rename / Edit…"), None);
+        menu_item.set_action_and_target_value(
+            Some("win.show-collection-editor"),
+            Some(&collection_object.get_id().to_variant()),
+        );
+        self.rightclick_menu_model.append_item(&menu_item);
+
+        let menu_item = MenuItem::new(Some("Copy as Markdown"), None);
+        menu_item.set_action_and_target_value(
+            Some("win.copy-markdown"),
+            Some(&collection_object.get_id().to_variant()),
+        );
+        self.rightclick_menu_model.append_item(&menu_item);
 
         let menu_item = MenuItem::new(Some("Delete"), None);
         menu_item.set_action_and_target_value(
@@ -38,6 +86,49 @@ impl CollectionRowImp {
             Some(&collection_object.get_id().to_variant()),
         );
         self.rightclick_menu_model.append_item(&menu_item);
+
+        for format in Format::ALL {
+            let menu_item = MenuItem::new(Some(&format!("Export as {}…", format.label())), None);
+            menu_item.set_action_and_target_value(
+                Some("win.export-collection"),
+                Some(&(collection_object.get_id(), Into::<&str>::into(format)).to_variant()),
+            );
+            self.rightclick_menu_model.append_item(&menu_item);
+        }
+    }
+
+    /// Stop following the collection shown so far
+    fn release(&self) {
+        if let Some(binding) = self.title_binding.take() {
+            binding.unbind();
+        }
+        if let Some(collection_object) = self.collection_object.take() {
+            for handler in self.handlers.take() {
+                collection_object.disconnect(handler);
+            }
+        }
+    }
+
+    /// Show the icon of the collection in its color, or a dot if it only has a color
+    fn update_icon(&self, collection_object: &CollectionObject) {
+        let color = collection_object.color();
+        let icon = collection_object.icon();
+
+        for class in self.icon_image.css_classes() {
+            if class != "collection-color" {
+                self.icon_image.remove_css_class(&class);
+            }
+        }
+        if !color.is_empty() {
+            self.icon_image.add_css_class(&color);
+        }
+        self.icon_image.set_icon_name(Some(if icon.is_empty() {
+            "media-record-symbolic"
+        } else {
+            &icon
+        }));
+        self.icon_image
+            .set_visible(!color.is_empty() || !icon.is_empty());
     }
 
     pub(super) fn copy(&self) {
@@ -90,6 +181,7 @@ impl ObjectImpl for CollectionRowImp {
             .append(Some("Copy"), Some("row.copy"));
     }
     fn dispose(&self) {
+        self.release();
         self.rightclick_menu.unparent();
     }
 }
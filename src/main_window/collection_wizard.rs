@@ -1,6 +1,9 @@
 use adw::Dialog;
+use adw::subclass::prelude::*;
 use gtk::glib::{self, Object};
 
+use crate::collection_object::CollectionObject;
+
 glib::wrapper! {
     pub struct CollectionWizard(ObjectSubclass<collection_wizard_imp::CollectionWizardImp>)
         @extends Dialog, gtk::Widget,
@@ -11,6 +14,15 @@ impl CollectionWizard {
     pub fn new() -> Self {
         Object::builder().build()
     }
+
+    /// Wizard changing the title, color and icon of `collection_object`
+    pub fn edit(collection_object: &CollectionObject) -> Self {
+        let self_ = Self::new();
+
+        self_.imp().edit(collection_object);
+
+        self_
+    }
 }
 
 mod collection_wizard_imp;
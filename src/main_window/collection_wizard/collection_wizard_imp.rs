@@ -1,3 +1,5 @@
+use std::cell::RefCell;
+
 use adw::prelude::AdwDialogExt;
 use adw::{Dialog, subclass::prelude::*};
 use glib::object::ObjectExt;
@@ -6,8 +8,34 @@ use glib::variant::ToVariant;
 use gtk::Button;
 use gtk::CompositeTemplate;
 use gtk::Entry;
+use gtk::FlowBox;
+use gtk::ToggleButton;
 use gtk::glib;
-use gtk::prelude::{EditableExt, WidgetExt};
+use gtk::prelude::{ButtonExt, EditableExt, ToggleButtonExt, WidgetExt};
+
+use crate::collection_object::CollectionObject;
+
+/// Accent colors a collection can have, as named by libadwaita
+const COLORS: [&str; 9] = [
+    "blue", "teal", "green", "yellow", "orange", "red", "pink", "purple", "slate",
+];
+
+/// Icons a collection can have
+const ICONS: [&str; 13] = [
+    "folder-symbolic",
+    "user-home-symbolic",
+    "starred-symbolic",
+    "emblem-important-symbolic",
+    "emblem-documents-symbolic",
+    "mail-unread-symbolic",
+    "x-office-calendar-symbolic",
+    "emblem-system-symbolic",
+    "applications-games-symbolic",
+    "applications-science-symbolic",
+    "audio-x-generic-symbolic",
+    "camera-photo-symbolic",
+    "face-smile-symbolic",
+];
 
 #[derive(CompositeTemplate, Default)]
 #[template(resource = "/me/gtk-rs-test/test/collection_wizard.ui")]
@@ -16,6 +44,15 @@ pub struct CollectionWizardImp {
     create_button: TemplateChild<Button>,
     #[template_child]
     entry: TemplateChild<Entry>,
+    #[template_child]
+    color_box: TemplateChild<FlowBox>,
+    #[template_child]
+    icon_box: TemplateChild<FlowBox>,
+
+    /// Collection being edited, none while creating a new one
+    collection_object: RefCell<Option<CollectionObject>>,
+    color_buttons: RefCell<Vec<ToggleButton>>,
+    icon_buttons: RefCell<Vec<ToggleButton>>,
 }
 
 #[gtk::template_callbacks]
@@ -33,16 +70,46 @@ impl CollectionWizardImp {
         self.done();
     }
 
+    /// Edit `collection_object` instead of creating a new collection
+    pub(super) fn edit(&self, collection_object: &CollectionObject) {
+        self.obj().set_title("Edit Collection");
+        self.create_button.set_label("Save");
+        self.entry.set_text(&collection_object.title());
+        select(
+            &self.color_buttons.borrow(),
+            &COLORS,
+            &collection_object.color(),
+        );
+        select(
+            &self.icon_buttons.borrow(),
+            &ICONS,
+            &collection_object.icon(),
+        );
+
+        self.collection_object
+            .replace(Some(collection_object.clone()));
+    }
+
     fn done(&self) {
         let title = self.entry.text();
         let title = title.trim();
         if title.len() == 0 {
             return;
         }
+        let color = chosen(&self.color_buttons.borrow(), &COLORS);
+        let icon = chosen(&self.icon_buttons.borrow(), &ICONS);
 
-        self.obj()
-            .activate_action("win.add-collection", Some(&title.to_variant()))
-            .unwrap();
+        match &*self.collection_object.borrow() {
+            Some(collection_object) => self.obj().activate_action(
+                "win.edit-collection",
+                Some(&(collection_object.get_id(), title, color, icon).to_variant()),
+            ),
+            None => self.obj().activate_action(
+                "win.add-collection",
+                Some(&(title, color, icon).to_variant()),
+            ),
+        }
+        .unwrap();
 
         self.obj().close();
     }
@@ -51,6 +118,42 @@ impl CollectionWizardImp {
     }
 }
 
+/// Add a toggle button built by `build` for each of `values` to `flow_box`, preceded
+/// by one choosing none of them
+fn fill(
+    flow_box: &FlowBox,
+    values: &[&'static str],
+    build: impl Fn(&'static str) -> ToggleButton,
+) -> Vec<ToggleButton> {
+    let mut buttons: Vec<ToggleButton> = Vec::new();
+    for value in std::iter::once("").chain(values.iter().copied()) {
+        let button = build(value);
+        match buttons.first() {
+            Some(first) => button.set_group(Some(first)),
+            None => button.set_active(true),
+        }
+        flow_box.append(&button);
+        buttons.push(button);
+    }
+
+    buttons
+}
+
+/// Value of the active one of the buttons added by `fill`, empty for none
+fn chosen(buttons: &[ToggleButton], values: &[&str]) -> String {
+    buttons
+        .iter()
+        .position(|b| b.is_active())
+        .and_then(|i| values.get(i.checked_sub(1)?))
+        .map_or_else(String::new, |v| v.to_string())
+}
+
+/// Activate the one of the buttons added by `fill` for `value`
+fn select(buttons: &[ToggleButton], values: &[&str], value: &str) {
+    let i = values.iter().position(|v| *v == value).map_or(0, |i| i + 1);
+    buttons[i].set_active(true);
+}
+
 #[glib::object_subclass]
 impl ObjectSubclass for CollectionWizardImp {
     const NAME: &'static str = "GtkRsTestCollectionWizard";
@@ -86,6 +189,33 @@ impl ObjectImpl for CollectionWizardImp {
                 })
             })
             .build();
+
+        let color_buttons = fill(&self.color_box, &COLORS, |color| {
+            let button = ToggleButton::builder()
+                .css_classes(["circular", "collection-color"])
+                .tooltip_text(color)
+                .build();
+            if color.is_empty() {
+                button.set_icon_name("window-close-symbolic");
+                button.set_tooltip_text(Some("No Color"));
+            } else {
+                button.add_css_class(color);
+            }
+            button
+        });
+        self.color_buttons.replace(color_buttons);
+        let icon_buttons = fill(&self.icon_box, &ICONS, |icon| {
+            let button = ToggleButton::builder()
+                .icon_name(icon)
+                .css_classes(["flat"])
+                .build();
+            if icon.is_empty() {
+                button.set_icon_name("action-unavailable-symbolic");
+                button.set_tooltip_text(Some("No Icon"));
+            }
+            button
+        });
+        self.icon_buttons.replace(icon_buttons);
     }
 }
 impl WidgetImpl for CollectionWizardImp {}
@@ -0,0 +1,33 @@
+use adw::Dialog;
+use adw::subclass::prelude::*;
+use gtk::{
+    gio::ListStore,
+    glib::{self, Object},
+};
+
+use super::MainWindow;
+
+glib::wrapper! {
+    pub struct CsvImportDialog(ObjectSubclass<csv_import_dialog_imp::CsvImportDialogImp>)
+        @extends Dialog, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::ShortcutManager;
+}
+
+impl CsvImportDialog {
+    /// Dialog importing `rows` of a CSV file, the first of which is the header row,
+    /// into one of `collections` of `window` or a new collection titled `default_title`
+    pub fn new(
+        rows: Vec<Vec<String>>,
+        default_title: &str,
+        collections: &ListStore,
+        window: &MainWindow,
+    ) -> Self {
+        let self_: Self = Object::builder().build();
+
+        self_.imp().fill(rows, default_title, collections, window);
+
+        self_
+    }
+}
+
+mod csv_import_dialog_imp;
@@ -0,0 +1,159 @@
+use std::cell::RefCell;
+
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use adw::{ComboRow, Dialog, PreferencesGroup};
+use glib::subclass::InitializingObject;
+use gtk::Button;
+use gtk::CompositeTemplate;
+use gtk::StringList;
+use gtk::gio::ListStore;
+use gtk::glib;
+use gtk::glib::WeakRef;
+
+use crate::{
+    collection_object::{self, CollectionObject},
+    formats::csv::{self, Column},
+    main_window::MainWindow,
+};
+
+#[derive(CompositeTemplate, Default)]
+#[template(resource = "/me/gtk-rs-test/test/csv_import_dialog.ui")]
+pub struct CsvImportDialogImp {
+    #[template_child]
+    import_button: TemplateChild<Button>,
+    #[template_child]
+    collection_row: TemplateChild<ComboRow>,
+    #[template_child]
+    columns_group: TemplateChild<PreferencesGroup>,
+
+    /// Rows of the file without the header row
+    rows: RefCell<Vec<Vec<String>>>,
+    default_title: RefCell<String>,
+    /// Ids of the collections offered by `collection_row`, after the new collection
+    collection_ids: RefCell<Vec<collection_object::IdType>>,
+    /// A row per column of the file, choosing the property it is read into
+    column_rows: RefCell<Vec<ComboRow>>,
+    /// Window the tasks are imported into
+    window: WeakRef<MainWindow>,
+}
+
+#[gtk::template_callbacks]
+impl CsvImportDialogImp {
+    #[template_callback]
+    fn handle_import_button_clicked(&self) {
+        self.done();
+    }
+    #[template_callback]
+    fn handle_cancel_button_clicked(&self) {
+        self.obj().close();
+    }
+
+    pub(super) fn fill(
+        &self,
+        mut rows: Vec<Vec<String>>,
+        default_title: &str,
+        collections: &ListStore,
+        window: &MainWindow,
+    ) {
+        self.window.set(Some(window));
+        let header = if rows.is_empty() {
+            Vec::new()
+        } else {
+            rows.remove(0)
+        };
+
+        let titles = StringList::new(&[&format!("New Collection “{default_title}”")]);
+        for collection_object in collections.iter::<CollectionObject>().map(Result::unwrap) {
+            titles.append(&collection_object.title());
+            self.collection_ids
+                .borrow_mut()
+                .push(collection_object.get_id());
+        }
+        self.collection_row.set_model(Some(&titles));
+
+        let labels: Vec<&str> = Column::ALL.iter().map(|c| c.label()).collect();
+        for (i, header) in header.iter().enumerate() {
+            let title = match header.trim() {
+                "" => format!("Column {}", i + 1),
+                header => header.to_string(),
+            };
+            // Show the first value as an example of the column's content
+            let example = rows
+                .iter()
+                .filter_map(|fields| fields.get(i))
+                .find(|f| !f.trim().is_empty());
+            let row = ComboRow::builder()
+                .title(title)
+                .subtitle(example.map(String::as_str).unwrap_or_default())
+                .use_markup(false)
+                .model(&StringList::new(&labels))
+                .build();
+            let guess = Column::guess(header);
+            row.set_selected(Column::ALL.iter().position(|c| *c == guess).unwrap() as u32);
+
+            let dialog = self.obj().downgrade();
+            row.connect_selected_notify(move |_| {
+                if let Some(dialog) = dialog.upgrade() {
+                    dialog.imp().update_import_button();
+                }
+            });
+            self.columns_group.add(&row);
+            self.column_rows.borrow_mut().push(row);
+        }
+
+        *self.rows.borrow_mut() = rows;
+        *self.default_title.borrow_mut() = default_title.to_string();
+        self.update_import_button();
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        self.column_rows
+            .borrow()
+            .iter()
+            .map(|row| Column::ALL[row.selected() as usize])
+            .collect()
+    }
+
+    /// Tasks can only be imported once a column is read into their name
+    fn update_import_button(&self) {
+        self.import_button
+            .set_sensitive(self.columns().contains(&Column::Name));
+    }
+
+    fn done(&self) {
+        let tasks = csv::tasks(&self.rows.borrow(), &self.columns());
+        let id = match self.collection_row.selected() {
+            0 => None,
+            i => Some(self.collection_ids.borrow()[i as usize - 1].clone()),
+        };
+
+        if let Some(window) = self.window.upgrade() {
+            window
+                .imp()
+                .import_tasks(id, &self.default_title.borrow(), tasks);
+        }
+
+        self.obj().close();
+    }
+}
+
+#[glib::object_subclass]
+impl ObjectSubclass for CsvImportDialogImp {
+    const NAME: &'static str = "GtkRsTestCsvImportDialog";
+    type Type = super::CsvImportDialog;
+    type ParentType = Dialog;
+
+    fn class_init(klass: &mut Self::Class) {
+        klass.bind_template();
+        klass.bind_template_callbacks();
+    }
+
+    fn instance_init(obj: &InitializingObject<Self>) {
+        obj.init_template();
+    }
+}
+
+impl ObjectImpl for CsvImportDialogImp {}
+impl WidgetImpl for CsvImportDialogImp {}
+impl AdwDialogImpl for CsvImportDialogImp {}
@@ -1,15 +1,16 @@
 use std::{
     cell::{OnceCell, RefCell},
-    error::Error,
-    fs,
-    io::Write,
+    collections::{BTreeSet, HashMap, HashSet, hash_map},
+    path::Path,
 };
 
 use gtk::{
-    CompositeTemplate, CustomFilter, Entry, EntryIconPosition, FilterListModel, ListBox,
-    ListBoxRow, ListItem, NoSelection, Stack, TemplateChild, Widget,
-    gio::ListStore,
-    glib::{self, Properties, subclass::InitializingObject},
+    CallbackAction, CompositeTemplate, CustomFilter, CustomSorter, Entry, EntryIconPosition,
+    FileDialog, FileFilter, FilterListModel, Label, ListBox, ListBoxRow, ListItem, ListScrollFlags,
+    ListView, NoSelection, Shortcut, ShortcutController, ShortcutTrigger, SortListModel, Stack,
+    StringList, StringObject, TemplateChild, TreeListModel, TreeListRow, Widget,
+    gio::{self, FileCreateFlags, ListStore, SimpleAction},
+    glib::{self, Binding, DateTime, Properties, SignalHandlerId, subclass::InitializingObject},
     subclass::{
         widget::{CompositeTemplateClass, CompositeTemplateInitializingExt, WidgetImpl},
         window::WindowImpl,
@@ -17,20 +18,27 @@ use gtk::{
 };
 
 use adw::{
-    AboutDialog, ApplicationWindow, Banner, NavigationSplitView, Toast, ToastOverlay, prelude::*,
+    AboutDialog, AlertDialog, ApplicationWindow, Banner, NavigationSplitView, Toast, ToastOverlay,
+    prelude::*,
 };
 
 use adw::subclass::prelude::*;
-use gtk_rs_test::{list_store_ser::ListStoreSer, watcher::Watcher};
+use gtk_rs_test::watcher::Watcher;
 
 use crate::{
+    application::Application,
     collection_object::{self, CollectionObject},
-    data_path,
+    formats::{self, Format},
+    history::Command,
+    recurrence::Recurrence,
+    storage,
     task_object::{self, TaskObject},
 };
 
 use super::{
-    collection_row::CollectionRow, collection_wizard::CollectionWizard, task_row::TaskRow,
+    backup_dialog::BackupDialog, collection_row::CollectionRow,
+    collection_wizard::CollectionWizard, csv_import_dialog::CsvImportDialog,
+    task_detail::TaskDetail, task_row::TaskRow,
 };
 
 #[derive(PartialEq, Default)]
@@ -62,6 +70,44 @@ impl Into<&str> for FilterMode {
     }
 }
 
+#[derive(PartialEq, Default)]
+pub enum SortMode {
+    #[default]
+    Position,
+    Priority,
+    /// Most recently created first
+    Created,
+    /// Most recently modified first
+    Modified,
+    /// Most recently completed first, unresolved tasks last
+    Completed,
+}
+
+impl From<&str> for SortMode {
+    fn from(value: &str) -> Self {
+        match value {
+            "position" => SortMode::Position,
+            "priority" => SortMode::Priority,
+            "created" => SortMode::Created,
+            "modified" => SortMode::Modified,
+            "completed" => SortMode::Completed,
+            _ => panic!("Impossible"),
+        }
+    }
+}
+
+impl From<SortMode> for &str {
+    fn from(value: SortMode) -> Self {
+        match value {
+            SortMode::Position => "position",
+            SortMode::Priority => "priority",
+            SortMode::Created => "created",
+            SortMode::Modified => "modified",
+            SortMode::Completed => "completed",
+        }
+    }
+}
+
 #[derive(CompositeTemplate, Properties)]
 #[properties(wrapper_type = super::MainWindow)]
 #[template(resource = "/me/gtk-rs-test/test/main_window.ui")]
@@ -69,6 +115,8 @@ pub struct MainWindowImp {
     #[template_child]
     task_model: TemplateChild<NoSelection>,
     #[template_child]
+    task_list: TemplateChild<ListView>,
+    #[template_child]
     toast: TemplateChild<ToastOverlay>,
     #[template_child]
     banner: TemplateChild<Banner>,
@@ -79,16 +127,40 @@ pub struct MainWindowImp {
     #[template_child]
     collection_list_box: TemplateChild<ListBox>,
     #[template_child]
+    tag_heading: TemplateChild<Label>,
+    #[template_child]
+    tag_list_box: TemplateChild<ListBox>,
+    #[template_child]
     split_view: TemplateChild<NavigationSplitView>,
     #[property(get, set)]
     filter_mode: RefCell<String>,
+    #[property(get, set)]
+    sort_mode: RefCell<String>,
 
     #[property(get, set)]
     task_page_title: RefCell<String>,
 
     selected_collection: RefCell<Watcher<'static, Option<CollectionObject>>>,
-    collections: ListStore,
+    selected_collection_handler: RefCell<Option<(CollectionObject, SignalHandlerId)>>,
+    /// Keeps the title of the task page in line with the title of the selected collection
+    selected_collection_title: RefCell<Option<Binding>>,
+    /// Collections of the application, shared with its other windows
+    collections: OnceCell<ListStore>,
+    /// Handler of changes to `collections`, disconnected when the window goes away
+    collections_handler: RefCell<Option<SignalHandlerId>>,
+    /// Collections whose changes are reflected in the tag list, with the handlers
+    /// of their `tasks-changed` and `task-notify` signals
+    ///
+    /// Kept by object rather than id, as restoring a backup brings in new objects
+    /// with known ids.
+    watched_collections: RefCell<HashMap<CollectionObject, (SignalHandlerId, SignalHandlerId)>>,
+    /// Every tag used across all collections, sorted
+    tags: StringList,
+    selected_tag: RefCell<Option<String>>,
+    /// Tasks from every collection carrying the selected tag
+    tag_tasks: ListStore,
     task_filter: OnceCell<CustomFilter>,
+    task_sorter: OnceCell<CustomSorter>,
 }
 
 #[gtk::template_callbacks]
@@ -107,9 +179,10 @@ impl MainWindowImp {
     }
     #[template_callback]
     fn handle_task_list_factory_bind(&self, list_item: &ListItem) {
-        let task_object = list_item.item().and_downcast::<TaskObject>().unwrap();
+        let list_row = list_item.item().and_downcast::<TreeListRow>().unwrap();
+        let task_object = list_row.item().and_downcast::<TaskObject>().unwrap();
         let task_row = list_item.child().and_downcast::<TaskRow>().unwrap();
-        task_row.bind(&task_object);
+        task_row.bind(&task_object, &list_row);
     }
     #[template_callback]
     fn handle_task_list_factory_unbind(&self, list_item: &ListItem) {
@@ -117,6 +190,18 @@ impl MainWindowImp {
         task_row.unbind();
     }
     #[template_callback]
+    fn handle_task_list_activated(&self, position: u32) {
+        if let Some(task_object) = self
+            .task_model
+            .item(position)
+            .and_downcast::<TreeListRow>()
+            .and_then(|list_row| list_row.item())
+            .and_downcast::<TaskObject>()
+        {
+            self.show_task_details(&task_object);
+        }
+    }
+    #[template_callback]
     fn handle_banner_button_clicked(&self) {
         self.obj()
             .set_filter_mode(Into::<&str>::into(FilterMode::All).to_string());
@@ -127,8 +212,11 @@ impl MainWindowImp {
     }
     #[template_callback]
     fn handle_collection_row_selected(&self, list_box_row: Option<&ListBoxRow>) {
+        if list_box_row.is_some() {
+            self.tag_list_box.unselect_all();
+        }
         *self.selected_collection.borrow_mut().borrow_mut() = list_box_row.map(|list_box_row| {
-            self.collections
+            self.collections()
                 .item(list_box_row.index().try_into().unwrap())
                 .and_downcast::<CollectionObject>()
                 .unwrap()
@@ -138,6 +226,121 @@ impl MainWindowImp {
     fn handle_collection_row_activated(&self) {
         self.split_view.set_show_content(true);
     }
+    #[template_callback]
+    fn handle_tag_row_selected(&self, list_box_row: Option<&ListBoxRow>) {
+        let Some(list_box_row) = list_box_row else {
+            self.selected_tag.replace(None);
+            return;
+        };
+        let tag = self.tags.string(list_box_row.index() as u32).unwrap();
+
+        self.selected_tag.replace(Some(tag.to_string()));
+        self.collection_list_box.unselect_all();
+        self.update_tag_tasks();
+
+        let tree = self.task_tree(&self.tag_tasks);
+        self.task_model.set_model(Some(&tree));
+        self.obj()
+            .set_task_page_title(format!("Tasks tagged #{tag}"));
+    }
+
+    /// Tree of `tasks` and their subtasks, honoring the current filter and sort order
+    fn task_tree(&self, tasks: &ListStore) -> TreeListModel {
+        let window = self.downgrade();
+        TreeListModel::new(self.sorted_tasks(tasks), false, false, move |task_object| {
+            let task_object = task_object.downcast_ref::<TaskObject>().unwrap();
+            let window = window.upgrade().unwrap();
+            Some(window.sorted_tasks(&task_object.subtasks()).upcast())
+        })
+    }
+
+    /// Rebuild the list of tags, and the tasks shown for the selected tag
+    fn refresh_tags(&self) {
+        fn collect(tasks: &ListStore, tags: &mut BTreeSet<String>) {
+            for task_object in tasks.iter::<TaskObject>().map(Result::unwrap) {
+                tags.extend(task_object.tags());
+                collect(&task_object.subtasks(), tags);
+            }
+        }
+
+        let mut tags = BTreeSet::new();
+        for c in self
+            .collections()
+            .iter::<CollectionObject>()
+            .map(Result::unwrap)
+        {
+            collect(&c.tasks(), &mut tags);
+        }
+
+        let current: Vec<String> = self
+            .tags
+            .iter::<StringObject>()
+            .map(|s| s.unwrap().string().to_string())
+            .collect();
+        if !current.iter().eq(tags.iter()) {
+            let selected_tag = self.selected_tag.take();
+            let tags: Vec<&str> = tags.iter().map(String::as_str).collect();
+            self.tags.splice(0, self.tags.n_items(), &tags);
+
+            // Rows are rebuilt by the splice, restore the selection
+            match selected_tag.and_then(|tag| tags.iter().position(|t| *t == tag)) {
+                Some(index) => self
+                    .tag_list_box
+                    .select_row(self.tag_list_box.row_at_index(index as i32).as_ref()),
+                None if self.selected_collection.borrow().is_none() => {
+                    self.task_model.set_model(None::<&TreeListModel>);
+                    self.obj().set_task_page_title("Tasks");
+                }
+                None => {}
+            }
+        }
+
+        self.update_tag_tasks();
+    }
+
+    fn update_tag_tasks(&self) {
+        fn collect(tasks: &ListStore, tag: &str, found: &mut Vec<TaskObject>) {
+            for task_object in tasks.iter::<TaskObject>().map(Result::unwrap) {
+                if task_object.has_tag(tag) {
+                    found.push(task_object.clone());
+                }
+                collect(&task_object.subtasks(), tag, found);
+            }
+        }
+
+        let mut found = Vec::new();
+        if let Some(tag) = &*self.selected_tag.borrow() {
+            for c in self
+                .collections()
+                .iter::<CollectionObject>()
+                .map(Result::unwrap)
+            {
+                collect(&c.tasks(), tag, &mut found);
+            }
+        }
+
+        let current: Vec<TaskObject> = self
+            .tag_tasks
+            .iter::<TaskObject>()
+            .map(Result::unwrap)
+            .collect();
+        if current != found {
+            self.tag_tasks.splice(0, self.tag_tasks.n_items(), &found);
+        }
+    }
+
+    /// Wrap `tasks` with the current filter and sort order of the task list
+    fn sorted_tasks(&self, tasks: &ListStore) -> SortListModel {
+        let filtered = FilterListModel::new(
+            Some(tasks.clone()),
+            Some(self.task_filter.get().unwrap().clone()),
+        );
+
+        SortListModel::new(
+            Some(filtered),
+            Some(self.task_sorter.get().unwrap().clone()),
+        )
+    }
 
     fn update_banner(&self) {
         match self.filter_mode.borrow().as_str().into() {
@@ -159,15 +362,57 @@ impl MainWindowImp {
             None => return,
         };
 
-        let name = self.task_entry.text();
-        let name = name.trim();
+        // Words starting with `#` become tags of the new task, unless they are numbers
+        // such as in "Fix #1"
+        let text = self.task_entry.text();
+        let (tags, words): (Vec<&str>, Vec<&str>) = text.split_whitespace().partition(|word| {
+            word.strip_prefix('#')
+                .is_some_and(|tag| tag.chars().any(|c| !c.is_ascii_digit()))
+        });
+        let name = words.join(" ");
         if name.len() == 0 {
             return;
         }
-        tasks.append(&TaskObject::new(name));
+        let task_object = TaskObject::new(&name);
+        task_object.set_tags(task_object::parse_tags(&tags.join(" ")));
+        self.execute(Command::InsertTask {
+            position: tasks.n_items(),
+            tasks,
+            task_object,
+        });
         self.task_entry.set_text("");
 
-        self.show_toast(&format!("Task Added: {name}"), None);
+        self.show_undo_toast(&format!("Task Added: {name}"));
+    }
+    /// Add a task per item of a Markdown checklist pasted into the task entry
+    ///
+    /// Returns whether `text` was a checklist, otherwise it's left to be inserted as usual.
+    fn paste_checklist(&self, text: &str) -> bool {
+        let tasks = match &**self.selected_collection.borrow() {
+            Some(t) => t.tasks(),
+            None => return false,
+        };
+        let Some(pasted) = formats::markdown::import(text) else {
+            return false;
+        };
+
+        let count = pasted.len();
+        let commands = pasted
+            .into_iter()
+            .enumerate()
+            .map(|(i, task_object)| Command::InsertTask {
+                position: tasks.n_items() + i as u32,
+                tasks: tasks.clone(),
+                task_object,
+            })
+            .collect();
+        self.execute(Command::Group(commands));
+
+        self.show_undo_toast(&match count {
+            1 => String::from("Added 1 task"),
+            n => format!("Added {n} tasks"),
+        });
+        true
     }
     fn show_add_new_collection_dialog(&self) {
         CollectionWizard::new().present(Some(&*self.obj()));
@@ -175,34 +420,99 @@ impl MainWindowImp {
 
     /// Save state to filesystem
     pub(super) fn save(&self) {
-        let mut path = data_path();
+        self.application().save();
+    }
+    fn show_load_error_dialog(&self, body: &str, has_backups: bool) {
+        let dialog = AlertDialog::builder()
+            .heading("Could Not Read Collections")
+            .body(body)
+            .build();
+        dialog.add_response("close", "Close");
+        if has_backups {
+            dialog.add_response("backups", "Restore Backup…");
+            let window = self.downgrade();
+            dialog.connect_response(Some("backups"), move |_, _| {
+                if let Some(window) = window.upgrade() {
+                    window.show_backups_dialog();
+                }
+            });
+        }
 
-        let result = (|| -> Result<(), Box<dyn Error>> {
-            fs::create_dir_all(&path)?;
-            path.push("collections.json");
-            let v = serde_json::to_vec(&ListStoreSer::<CollectionObject>::new(
-                self.collections.clone(),
-            ))?;
+        dialog.present(Some(&*self.obj()));
+    }
+    pub(super) fn show_backups_dialog(&self) {
+        BackupDialog::new().present(Some(&*self.obj()));
+    }
+    /// Replace all collections with the ones stored in the backup at `path`
+    pub(super) fn restore_backup(&self, path: &str) {
+        let restored = match storage::load_from(Path::new(path)) {
+            Ok(restored) => restored,
+            Err(err) => {
+                eprintln!("Error occurred trying to restore backup: {err}");
+                self.show_toast("Could not restore backup", None);
+                return;
+            }
+        };
+        // Restoring is the explicit choice to replace data which couldn't be read, as
+        // long as that data can still be moved aside
+        if let Err(err) = storage::allow_saving() {
+            eprintln!("Error occurred trying to move the data file aside: {err}");
+            self.show_toast(
+                "Could not restore backup, the unreadable data file could not be moved aside",
+                None,
+            );
+            return;
+        }
+        // Keep the current state around in case restoring is regretted after quitting
+        if let Err(err) = storage::save_backup(self.collections()) {
+            eprintln!("Error occurred trying to back up collections: {err}")
+        }
 
-            fs::OpenOptions::new()
-                .write(true)
-                .truncate(true)
-                .open(path)?
-                .write_all(v.as_slice())?;
+        let mut commands: Vec<Command> = (0..self.collections().n_items())
+            .rev()
+            .map(|position| Command::RemoveCollection {
+                collections: self.collections().clone(),
+                position,
+                collection_object: self
+                    .collections()
+                    .item(position)
+                    .and_downcast::<CollectionObject>()
+                    .unwrap(),
+            })
+            .collect();
+        commands.extend(
+            restored
+                .into_iter()
+                .enumerate()
+                .map(|(position, collection_object)| Command::InsertCollection {
+                    collections: self.collections().clone(),
+                    position: position as u32,
+                    collection_object,
+                }),
+        );
+        self.execute(Command::Group(commands));
 
-            Ok(())
-        })();
-        if let Err(err) = result {
-            eprintln!("Error occurred trying to save collections: {err}")
-        }
+        self.show_undo_toast("Backup Restored");
     }
 
     pub(super) fn remove_collection_by_id(&self, id: collection_object::IdType) {
-        self.collections.retain(|c| {
-            let c = c.downcast_ref::<CollectionObject>().unwrap();
+        let Some((position, collection_object)) = self
+            .collections()
+            .iter::<CollectionObject>()
+            .map(Result::unwrap)
+            .enumerate()
+            .find(|(_, c)| c.get_id() == id)
+        else {
+            return;
+        };
 
-            c.get_id() != id
+        let title = collection_object.title();
+        self.execute(Command::RemoveCollection {
+            collections: self.collections().clone(),
+            position: position as u32,
+            collection_object,
         });
+        self.show_undo_toast(&format!("Collection Deleted: {title}"));
     }
     pub(super) fn remove_done_tasks(&self) {
         let tasks = match &**self.selected_collection.borrow() {
@@ -210,8 +520,343 @@ impl MainWindowImp {
             None => return,
         };
 
-        tasks.retain(|task_object| !task_object.downcast_ref::<TaskObject>().unwrap().checked());
-        self.show_toast("Removed all done tasks", None);
+        // Removed back to front, so that reverting in reverse order restores the positions
+        fn remove_done(tasks: &ListStore, removed: &mut Vec<Command>) {
+            for position in (0..tasks.n_items()).rev() {
+                let task_object = tasks.item(position).and_downcast::<TaskObject>().unwrap();
+                if task_object.checked() {
+                    removed.push(Command::RemoveTask {
+                        tasks: tasks.clone(),
+                        position,
+                        task_object,
+                    });
+                } else {
+                    remove_done(&task_object.subtasks(), removed);
+                }
+            }
+        }
+
+        let mut removed = Vec::new();
+        remove_done(&tasks, &mut removed);
+        if removed.is_empty() {
+            return;
+        }
+        self.execute(Command::Group(removed));
+        self.show_undo_toast("Removed all done tasks");
+    }
+    /// Ask for a file in `format` and add the collections and tasks it contains
+    ///
+    /// Tasks of collections with a known title are added to the existing collection.
+    pub(super) fn show_import_dialog(&self, format: Format) {
+        let dialog = FileDialog::builder()
+            .title(format!("Import {}", format.label()))
+            .filters(&file_filters(format))
+            .modal(true)
+            .build();
+
+        let window = self.downgrade();
+        glib::spawn_future_local(async move {
+            let Some(window) = window.upgrade() else {
+                return;
+            };
+            // Dismissing the dialog is reported as an error as well
+            let Ok(file) = dialog.open_future(Some(&*window.obj())).await else {
+                return;
+            };
+            let default_title = file
+                .basename()
+                .and_then(|name| Some(name.file_stem()?.to_string_lossy().into_owned()))
+                .unwrap_or_else(|| String::from("Imported"));
+
+            let imported = match read_file(&file).await {
+                // The columns of CSV files are mapped to properties by the user
+                Ok(text) if format == Format::Csv => match formats::csv::parse(&text) {
+                    Ok(rows) => {
+                        CsvImportDialog::new(
+                            rows,
+                            &default_title,
+                            window.collections(),
+                            &window.obj(),
+                        )
+                        .present(Some(&*window.obj()));
+                        return;
+                    }
+                    Err(err) => Err(err),
+                },
+                Ok(text) => format.import(&text, &default_title),
+                Err(err) => Err(err),
+            };
+            match imported {
+                Ok(collections) => window.import(collections, format.merges_collections()),
+                Err(err) => {
+                    eprintln!("Error occurred trying to import {}: {err}", format.label());
+                    window.show_toast(&format!("Could not import {}", format.label()), None);
+                }
+            }
+        });
+    }
+    /// Add imported collections, adding their tasks to existing collections of the same
+    /// id, or of the same title if `merge` is set
+    ///
+    /// Tasks with the id of an existing task, as they were imported before, update it
+    /// instead. Tasks with the id of an earlier imported task are skipped, subtasks with
+    /// a taken id get a new one.
+    fn import(&self, imported: Vec<CollectionObject>, merge: bool) {
+        let mut commands = Vec::new();
+        let mut count = 0;
+        let mut updated = 0;
+        let mut unchanged = 0;
+        let mut duplicates = 0;
+        let mut seen: HashMap<task_object::IdType, TaskObject> = HashMap::new();
+        let mut position = self.collections().n_items();
+        for collection_object in imported {
+            let tasks = collection_object.tasks();
+            let found = tasks.n_items();
+            let mut i = 0;
+            while i < tasks.n_items() {
+                let task_object = tasks.item(i).and_downcast::<TaskObject>().unwrap();
+                let id = task_object.get_id();
+                match seen.entry(id.clone()) {
+                    hash_map::Entry::Occupied(_) => {
+                        tasks.remove(i);
+                        duplicates += 1;
+                        continue;
+                    }
+                    hash_map::Entry::Vacant(entry) => {
+                        entry.insert(task_object.clone());
+                    }
+                }
+                if let Some((_, _, existing)) = self.find_task(&id) {
+                    tasks.remove(i);
+                    let changes = update_commands(&existing, &task_object);
+                    if changes.is_empty() {
+                        unchanged += 1;
+                    } else {
+                        commands.extend(changes);
+                        updated += 1;
+                    }
+                } else {
+                    count += 1 + self.claim_subtask_ids(&task_object, &mut seen);
+                    i += 1;
+                }
+            }
+            // Don't add collections whose tasks all exist already
+            if found > 0 && tasks.n_items() == 0 {
+                continue;
+            }
+            let existing = self
+                .collections()
+                .iter::<CollectionObject>()
+                .map(Result::unwrap)
+                .find(|c| {
+                    c.get_id() == collection_object.get_id()
+                        || merge && c.title() == collection_object.title()
+                });
+
+            match existing {
+                Some(existing) => {
+                    let tasks = existing.tasks();
+                    for (i, task_object) in collection_object
+                        .tasks()
+                        .iter::<TaskObject>()
+                        .map(Result::unwrap)
+                        .enumerate()
+                    {
+                        commands.push(Command::InsertTask {
+                            tasks: tasks.clone(),
+                            position: tasks.n_items() + i as u32,
+                            task_object,
+                        });
+                    }
+                }
+                None => {
+                    commands.push(Command::InsertCollection {
+                        collections: self.collections().clone(),
+                        position,
+                        collection_object,
+                    });
+                    position += 1;
+                }
+            }
+        }
+
+        let mut report = Vec::new();
+        if count > 0 {
+            report.push(format!("Imported {}", tasks_label(count)));
+        }
+        if updated > 0 {
+            report.push(format!("Updated {}", tasks_label(updated)));
+        }
+        if duplicates > 0 {
+            report.push(format!("Skipped {duplicates} with a duplicate id"));
+        }
+        if count == 0 && updated == 0 {
+            let message = if unchanged > 0 {
+                String::from("All tasks were imported before")
+            } else if duplicates > 0 {
+                format!("Nothing imported, skipped {duplicates} with a duplicate id")
+            } else {
+                String::from("No tasks found to import")
+            };
+            self.show_toast(&message, None);
+            return;
+        }
+        self.execute(Command::Group(commands));
+        self.show_undo_toast(&report.join(", "));
+    }
+    /// Record the subtasks of a newly imported task in `seen`, giving those whose id is
+    /// taken a new one, so that ids stay unique. Returns the number of subtasks.
+    fn claim_subtask_ids(
+        &self,
+        task_object: &TaskObject,
+        seen: &mut HashMap<task_object::IdType, TaskObject>,
+    ) -> u32 {
+        let mut count = 0;
+        for subtask in task_object
+            .subtasks()
+            .iter::<TaskObject>()
+            .map(Result::unwrap)
+        {
+            let id = subtask.get_id();
+            if seen.contains_key(&id) || self.find_task(&id).is_some() {
+                subtask.renew_id();
+            }
+            seen.insert(subtask.get_id(), subtask.clone());
+            count += 1 + self.claim_subtask_ids(&subtask, seen);
+        }
+
+        count
+    }
+    /// Add tasks read from a CSV file, each with the title of its collection
+    ///
+    /// Tasks without a title are added to the collection with the id `target`, or to a
+    /// new collection titled `default_title` if there is none. The others are added to
+    /// the collection of their title, which is created if there is none.
+    pub(super) fn import_tasks(
+        &self,
+        target: Option<collection_object::IdType>,
+        default_title: &str,
+        imported: Vec<(String, TaskObject)>,
+    ) {
+        let existing: Vec<CollectionObject> = self
+            .collections()
+            .iter::<CollectionObject>()
+            .map(Result::unwrap)
+            .collect();
+        let target = target.and_then(|id| existing.iter().find(|c| c.get_id() == id));
+
+        // Existing collections are stood in for by collections with their id
+        let mut collections: Vec<CollectionObject> = Vec::new();
+        for (title, task_object) in imported {
+            let (title, id) = match (title.as_str(), target) {
+                ("", Some(target)) => (target.title(), Some(target.get_id())),
+                ("", None) => (default_title.to_string(), None),
+                (title, _) => (
+                    title.to_string(),
+                    existing
+                        .iter()
+                        .find(|c| c.title() == title)
+                        .map(CollectionObject::get_id),
+                ),
+            };
+            let collection_object = match collections
+                .iter()
+                .find(|c| c.title() == title && id.as_ref().is_none_or(|id| c.get_id() == *id))
+            {
+                Some(c) => c.clone(),
+                None => {
+                    let c = match id {
+                        Some(id) => CollectionObject::with_id(&title, id),
+                        None => CollectionObject::new(&title),
+                    };
+                    collections.push(c.clone());
+                    c
+                }
+            };
+            collection_object.tasks().append(&task_object);
+        }
+
+        self.import(collections, false);
+    }
+    /// Ask for a file to write the collection with the given id, or all collections,
+    /// to in `format`
+    pub(super) fn show_export_dialog(&self, format: Format, id: Option<collection_object::IdType>) {
+        let collection_object = match id {
+            Some(id) => match self
+                .collections()
+                .iter::<CollectionObject>()
+                .map(Result::unwrap)
+                .find(|c| c.get_id() == id)
+            {
+                Some(c) => Some(c),
+                None => return,
+            },
+            None => None,
+        };
+        let file_name = match &collection_object {
+            Some(c) => format!("{}.{}", c.title(), format.suffixes()[0]),
+            None => format.file_name().to_string(),
+        };
+        let dialog = FileDialog::builder()
+            .title(format!("Export {}", format.label()))
+            .initial_name(file_name)
+            .filters(&file_filters(format))
+            .modal(true)
+            .build();
+
+        let window = self.downgrade();
+        glib::spawn_future_local(async move {
+            let Some(window) = window.upgrade() else {
+                return;
+            };
+            let Ok(file) = dialog.save_future(Some(&*window.obj())).await else {
+                return;
+            };
+
+            let collections: Vec<CollectionObject> = match collection_object {
+                Some(c) => vec![c],
+                None => window
+                    .collections()
+                    .iter::<CollectionObject>()
+                    .map(Result::unwrap)
+                    .collect(),
+            };
+            let contents = format.export(&collections);
+            match file
+                .replace_contents_future(
+                    contents.into_bytes(),
+                    None,
+                    false,
+                    FileCreateFlags::REPLACE_DESTINATION,
+                )
+                .await
+            {
+                Ok(_) => window.show_toast(&format!("Exported {}", format.label()), None),
+                Err((_, err)) => {
+                    eprintln!("Error occurred trying to export {}: {err}", format.label());
+                    window.show_toast(&format!("Could not export {}", format.label()), None);
+                }
+            }
+        });
+    }
+    /// Copy the tasks of a collection to the clipboard as a Markdown checklist
+    pub(super) fn copy_markdown(&self, id: collection_object::IdType) {
+        let Some(collection_object) = self
+            .collections()
+            .iter::<CollectionObject>()
+            .map(Result::unwrap)
+            .find(|c| c.get_id() == id)
+        else {
+            return;
+        };
+
+        self.obj()
+            .clipboard()
+            .set(&formats::markdown::export(&collection_object.tasks()));
+        self.show_toast(
+            &format!("Copied as Markdown: {}", collection_object.title()),
+            None,
+        );
     }
     pub(super) fn show_about_dialog(&self) {
         AboutDialog::builder()
@@ -222,44 +867,270 @@ impl MainWindowImp {
             .build()
             .present(Some(&*self.obj()));
     }
+    /// Find a task of any collection, see `task_object::find_task`
+    fn find_task(&self, id: &str) -> Option<(ListStore, u32, TaskObject)> {
+        self.collections()
+            .iter::<CollectionObject>()
+            .map(Result::unwrap)
+            .find_map(|c| task_object::find_task(&c.tasks(), id))
+    }
     pub(super) fn remove_task_by_id(&self, id: task_object::IdType) {
-        let tasks = match **self.selected_collection.borrow() {
-            Some(ref t) => t.tasks(),
-            None => return,
+        if let Some((tasks, position, task_object)) = self.find_task(&id) {
+            let name = task_object.name();
+            self.execute(Command::RemoveTask {
+                tasks,
+                position,
+                task_object,
+            });
+            self.show_undo_toast(&format!("Task Deleted: {name}"));
+        }
+    }
+    pub(super) fn rename_task(&self, id: task_object::IdType, name: &str) {
+        if let Some((_, _, task_object)) = self.find_task(&id)
+            && task_object.name() != name
+        {
+            self.execute(Command::set_property(&task_object, "name", name.to_value()));
+        }
+    }
+    /// Change `property` of a task to `value`, so that it can be undone
+    pub(super) fn set_task_property<T>(
+        &self,
+        id: task_object::IdType,
+        property: &'static str,
+        value: T,
+    ) where
+        T: ToValue + PartialEq + for<'a> glib::value::FromValue<'a> + 'static,
+    {
+        if let Some((_, _, task_object)) = self.find_task(&id)
+            && task_object.property::<T>(property) != value
+        {
+            self.execute(Command::set_property(
+                &task_object,
+                property,
+                value.to_value(),
+            ));
+        }
+    }
+    pub(super) fn add_subtask(&self, id: task_object::IdType, name: &str) {
+        let Some((_, _, task_object)) = self.find_task(&id) else {
+            return;
         };
 
-        let mut selected_task = None;
-        tasks.retain(|task| {
-            let task = task.downcast_ref::<TaskObject>().unwrap();
+        let subtasks = task_object.subtasks();
+        self.execute(Command::InsertTask {
+            position: subtasks.n_items(),
+            tasks: subtasks,
+            task_object: TaskObject::new(name),
+        });
+        self.show_undo_toast(&format!("Subtask Added: {name}"));
+    }
+    /// Move a task next to `target`, which has to have the same parent
+    pub(super) fn move_task(
+        &self,
+        id: task_object::IdType,
+        target: task_object::IdType,
+        after: bool,
+    ) {
+        if id == target || !self.can_reorder() {
+            return;
+        }
+        let (Some((tasks, from, _)), Some((target_tasks, position, _))) =
+            (self.find_task(&id), self.find_task(&target))
+        else {
+            return;
+        };
+        if tasks != target_tasks {
+            self.show_toast("Tasks can only be reordered among their siblings", None);
+            return;
+        }
 
-            if task.get_id() == id {
-                selected_task = Some(task.clone());
-            }
+        // The position of the target shifts once the task is taken out before it
+        let mut to = position + after as u32;
+        if from < to {
+            to -= 1;
+        }
+        self.reorder_task(tasks, from, to);
+    }
+    /// Move a task up or down past the next of its siblings shown in the task list
+    pub(super) fn move_task_by_step(&self, id: task_object::IdType, up: bool) {
+        let Some((tasks, from, _)) = self.find_task(&id) else {
+            return;
+        };
+        if !self.can_reorder() {
+            return;
+        }
 
-            task.get_id() != id
-        });
+        let filter = self.task_filter.get().unwrap();
+        let shown = |i: &u32| filter.match_(&tasks.item(*i).unwrap());
+        let to = if up {
+            (0..from).rev().find(shown)
+        } else {
+            (from + 1..tasks.n_items()).find(shown)
+        };
+        if let Some(to) = to {
+            self.reorder_task(tasks, from, to);
+        }
+    }
+    /// Tasks keep the order of their list only in a collection sorted by position
+    fn can_reorder(&self) -> bool {
+        if self.selected_tag.borrow().is_some() {
+            self.show_toast("Tasks can only be reordered within a collection", None);
+            false
+        } else if SortMode::from(self.sort_mode.borrow().as_str()) != SortMode::Position {
+            self.show_toast("Sort by position to reorder tasks", None);
+            false
+        } else {
+            true
+        }
+    }
+    /// Move the task at `from` of `tasks` to `to`, keeping its row expanded and focused
+    fn reorder_task(&self, tasks: ListStore, from: u32, to: u32) {
+        if from == to {
+            return;
+        }
+        let task_object = tasks.item(from).and_downcast::<TaskObject>().unwrap();
+        let expanded = self
+            .task_list_row(&task_object)
+            .is_some_and(|(_, list_row)| list_row.is_expanded());
+
+        self.execute(Command::MoveTask { tasks, from, to });
+
+        if let Some((position, list_row)) = self.task_list_row(&task_object) {
+            list_row.set_expanded(expanded);
+            self.task_list
+                .scroll_to(position, ListScrollFlags::FOCUS, None);
+        }
+    }
+    /// Position and row of `task_object` in the task list, if it is shown
+    fn task_list_row(&self, task_object: &TaskObject) -> Option<(u32, TreeListRow)> {
+        (0..self.task_model.n_items()).find_map(|position| {
+            let list_row = self
+                .task_model
+                .item(position)
+                .and_downcast::<TreeListRow>()?;
+            (list_row.item().as_ref() == Some(task_object.upcast_ref()))
+                .then_some((position, list_row))
+        })
+    }
+    /// Check or uncheck a task, checking a recurring task spawns its next occurrence
+    pub(super) fn check_task(&self, id: task_object::IdType, checked: bool) {
+        let Some((tasks, position, task_object)) = self.find_task(&id) else {
+            return;
+        };
+        if task_object.checked() == checked {
+            return;
+        }
 
-        if let Some(task) = selected_task {
-            self.show_toast(&format!("Task Deleted: {}", task.name()), None);
+        let mut commands = vec![
+            Command::set_property(&task_object, "checked", checked.to_value()),
+            // Checking sets the completion time and unchecking clears it, record it so
+            // that undoing restores the actual time instead of the time of undoing
+            Command::set_property(
+                &task_object,
+                "completed-at",
+                checked
+                    .then(|| DateTime::now_utc().ok())
+                    .flatten()
+                    .to_value(),
+            ),
+        ];
+        let next = checked.then(|| task_object.next_occurrence()).flatten();
+        if let Some(next) = &next {
+            // The completed occurrence is kept as a record and no longer repeats
+            commands.push(Command::set_property(
+                &task_object,
+                "recurrence",
+                None::<Recurrence>.to_value(),
+            ));
+            commands.push(Command::InsertTask {
+                tasks,
+                position: position + 1,
+                task_object: next.clone(),
+            });
+        }
+        self.execute(Command::Group(commands));
+
+        match next.map(|next| (next.due(), next)) {
+            Some((Some(due), _)) => {
+                self.show_undo_toast(&format!("Next Occurrence: {}", due.label()))
+            }
+            Some((None, next)) => {
+                self.show_undo_toast(&format!("Next Occurrence: {}", next.name()))
+            }
+            None => {}
+        }
+    }
+    pub(super) fn show_task_details_by_id(&self, id: task_object::IdType) {
+        if let Some((_, _, task_object)) = self.find_task(&id) {
+            self.show_task_details(&task_object);
         }
     }
-    pub(super) fn add_collection(&self, title: &str) {
+    fn show_task_details(&self, task_object: &TaskObject) {
+        TaskDetail::new(task_object, &self.obj()).present(Some(&*self.obj()));
+    }
+    pub(super) fn add_collection(&self, title: &str, color: &str, icon: &str) {
         let new_collection = CollectionObject::new(title);
-        self.collections.append(&new_collection);
+        new_collection.set_color(color);
+        new_collection.set_icon(icon);
+        self.execute(Command::InsertCollection {
+            collections: self.collections().clone(),
+            position: self.collections().n_items(),
+            collection_object: new_collection.clone(),
+        });
         *self.selected_collection.borrow_mut().borrow_mut() = Some(new_collection);
 
         self.collection_list_box.select_row(Some(
             &self
                 .collection_list_box
-                .row_at_index((self.collections.n_items() - 1) as i32)
+                .row_at_index((self.collections().n_items() - 1) as i32)
                 .unwrap(),
         ));
 
         self.set_focus_child(Some(self.task_entry.upcast_ref()));
     }
+    pub(super) fn show_collection_editor(&self, id: collection_object::IdType) {
+        if let Some(collection_object) = self
+            .collections()
+            .iter::<CollectionObject>()
+            .map(Result::unwrap)
+            .find(|c| c.get_id() == id)
+        {
+            CollectionWizard::edit(&collection_object).present(Some(&*self.obj()));
+        }
+    }
+    /// Change the title, color and icon of a collection
+    pub(super) fn edit_collection(
+        &self,
+        id: collection_object::IdType,
+        title: &str,
+        color: &str,
+        icon: &str,
+    ) {
+        let Some(collection_object) = self
+            .collections()
+            .iter::<CollectionObject>()
+            .map(Result::unwrap)
+            .find(|c| c.get_id() == id)
+        else {
+            return;
+        };
+
+        let commands: Vec<Command> = [("title", title), ("color", color), ("icon", icon)]
+            .into_iter()
+            .filter(|(property, value)| collection_object.property::<String>(property) != *value)
+            .map(|(property, value)| {
+                Command::set_property(&collection_object, property, value.to_value())
+            })
+            .collect();
+        if commands.is_empty() {
+            return;
+        }
+        self.execute(Command::Group(commands));
+        self.show_undo_toast(&format!("Collection Edited: {title}"));
+    }
     pub(super) fn select_collection(&self, id: collection_object::IdType) {
         let (index, collection_object) = match self
-            .collections
+            .collections()
             .iter::<CollectionObject>()
             .map(|c| c.unwrap())
             .enumerate()
@@ -283,24 +1154,265 @@ impl MainWindowImp {
                 .build(),
         );
     }
+    /// Show a toast offering to undo the change it reports
+    fn show_undo_toast(&self, content: &str) {
+        self.toast.add_toast(
+            Toast::builder()
+                .title(content)
+                .timeout(5)
+                .button_label("Undo")
+                .action_name("win.undo")
+                .build(),
+        );
+    }
+
+    /// Apply `command` and record it for undoing
+    fn execute(&self, command: Command) {
+        self.application().execute(command);
+    }
+    pub(super) fn undo(&self) {
+        self.application().undo();
+    }
+    pub(super) fn redo(&self) {
+        self.application().redo();
+    }
+
+    /// Reflect changes in the tasks of the collections in the tag list, following
+    /// the collections currently in the list
+    fn watch_collections(&self) {
+        let current: HashSet<CollectionObject> = self
+            .collections()
+            .iter::<CollectionObject>()
+            .map(Result::unwrap)
+            .collect();
+
+        let mut watched = self.watched_collections.borrow_mut();
+        for (c, (tasks_changed, task_notify)) in watched.extract_if(|c, _| !current.contains(c)) {
+            c.disconnect(tasks_changed);
+            c.disconnect(task_notify);
+        }
+
+        for c in current {
+            if watched.contains_key(&c) {
+                continue;
+            }
+
+            let window = self.downgrade();
+            let tasks_changed = c.connect_tasks_changed(move |_| {
+                if let Some(window) = window.upgrade() {
+                    window.refresh_tags();
+                }
+            });
+            let window = self.downgrade();
+            let task_notify = c.connect_task_notify(move |_, _, property| {
+                if let Some(window) = window.upgrade()
+                    && property == "tags"
+                {
+                    window.refresh_tags();
+                }
+            });
+            watched.insert(c, (tasks_changed, task_notify));
+        }
+        drop(watched);
+
+        self.refresh_tags();
+    }
+    /// Show the placeholder while there are no collections
+    fn update_stack(&self) {
+        self.stack
+            .set_visible_child_name(if self.collections().n_items() == 0 {
+                "placeholder"
+            } else {
+                "main"
+            });
+    }
+
+    /// Connect the window to the collections and history of its application
+    ///
+    /// `application` is not a construct property, so it is only set after `constructed`.
+    pub(super) fn setup_application(&self) {
+        self.collections
+            .set(self.application().collections())
+            .unwrap();
+
+        // Undo and redo are available while the shared history allows them
+        for (name, property) in [("undo", "can-undo"), ("redo", "can-redo")] {
+            let action = self
+                .obj()
+                .lookup_action(name)
+                .and_downcast::<SimpleAction>()
+                .unwrap();
+            self.application()
+                .bind_property(property, &action, "enabled")
+                .sync_create()
+                .build();
+        }
+
+        // Configure widget building for ListBox of collections
+        self.collection_list_box
+            .bind_model(Some(self.collections()), |collection_object| {
+                let collection_object = collection_object
+                    .downcast_ref::<CollectionObject>()
+                    .unwrap()
+                    .clone();
+
+                CollectionRow::new(&collection_object).upcast::<Widget>()
+            });
+
+        // Keep the tag list and the outer stack page up to date with the collections,
+        // which outlive the window as they are shared with the other windows
+        {
+            let window = self.downgrade();
+            let handler = self.collections().connect_items_changed(move |_, _, _, _| {
+                if let Some(window) = window.upgrade() {
+                    window.watch_collections();
+                    window.update_stack();
+                }
+            });
+            self.collections_handler.replace(Some(handler));
+        }
+        self.watch_collections();
+        self.update_stack();
+
+        // Tell about collections the application failed to load, dialogs need the
+        // window to be shown
+        if let Some((body, has_backups)) = self.application().take_load_error() {
+            let window = self.downgrade();
+            glib::idle_add_local_once(move || {
+                if let Some(window) = window.upgrade() {
+                    window.show_load_error_dialog(&body, has_backups);
+                }
+            });
+        }
+    }
+
+    fn application(&self) -> Application {
+        self.obj()
+            .application()
+            .and_downcast::<Application>()
+            .expect("windows belong to the application")
+    }
+    fn collections(&self) -> &ListStore {
+        self.collections.get().unwrap()
+    }
+}
+
+/// Filters of the file dialogs offering files in `format`
+fn file_filters(format: Format) -> ListStore {
+    let filter = FileFilter::new();
+    filter.set_name(Some(format.label()));
+    for suffix in format.suffixes() {
+        filter.add_suffix(suffix);
+    }
+
+    let filters = ListStore::new::<FileFilter>();
+    filters.append(&filter);
+
+    filters
+}
+
+/// Changes making `existing` match the fields of `imported`, which has the same id
+///
+/// Subtasks and the recurrence are kept, as not every format has them.
+fn update_commands(existing: &TaskObject, imported: &TaskObject) -> Vec<Command> {
+    let mut commands = Vec::new();
+    if existing.name() != imported.name() {
+        commands.push(Command::set_property(
+            existing,
+            "name",
+            imported.name().to_value(),
+        ));
+    }
+    if existing.checked() != imported.checked() {
+        commands.push(Command::set_property(
+            existing,
+            "checked",
+            imported.checked().to_value(),
+        ));
+    }
+    if existing.notes() != imported.notes() {
+        commands.push(Command::set_property(
+            existing,
+            "notes",
+            imported.notes().to_value(),
+        ));
+    }
+    if existing.due() != imported.due() {
+        commands.push(Command::set_property(
+            existing,
+            "due",
+            imported.due().to_value(),
+        ));
+    }
+    if existing.priority() != imported.priority() {
+        commands.push(Command::set_property(
+            existing,
+            "priority",
+            imported.priority().to_value(),
+        ));
+    }
+    if existing.tags() != imported.tags() {
+        commands.push(Command::set_property(
+            existing,
+            "tags",
+            imported.tags().to_value(),
+        ));
+    }
+
+    commands
+}
+
+/// Number of tasks, e.g. "1 task" or "3 tasks"
+fn tasks_label(count: u32) -> String {
+    match count {
+        1 => String::from("1 task"),
+        _ => format!("{count} tasks"),
+    }
+}
+
+/// Row of the task list which has the keyboard focus
+fn focused_task_row(list_view: &Widget) -> Option<TaskRow> {
+    list_view
+        .focus_child()
+        .and_then(|item| item.first_child())
+        .and_downcast::<TaskRow>()
+}
+
+/// Read the content of a text file
+async fn read_file(file: &gio::File) -> Result<String, formats::Error> {
+    let (contents, _) = file.load_contents_future().await?;
+
+    Ok(String::from_utf8(contents.to_vec())?)
 }
 
 impl Default for MainWindowImp {
     fn default() -> Self {
         Self {
             task_model: Default::default(),
+            task_list: Default::default(),
             toast: Default::default(),
             filter_mode: RefCell::new(String::from("all")),
+            sort_mode: RefCell::new(String::from("position")),
             banner: Default::default(),
             task_entry: Default::default(),
             stack: Default::default(),
             collection_list_box: Default::default(),
             selected_collection: Default::default(),
+            selected_collection_handler: Default::default(),
+            selected_collection_title: Default::default(),
             split_view: Default::default(),
+            tag_heading: Default::default(),
+            tag_list_box: Default::default(),
             task_page_title: RefCell::new(String::from("Tasks")),
 
             task_filter: Default::default(),
-            collections: ListStore::new::<CollectionObject>(),
+            task_sorter: Default::default(),
+            collections: Default::default(),
+            collections_handler: Default::default(),
+            watched_collections: Default::default(),
+            tags: StringList::new(&[]),
+            selected_tag: Default::default(),
+            tag_tasks: ListStore::new::<TaskObject>(),
         }
     }
 }
@@ -327,6 +1439,55 @@ impl ObjectImpl for MainWindowImp {
         self.parent_constructed();
 
         self.obj().setup_actions();
+        // Pasting a checklist adds its items instead of inserting them into the entry
+        {
+            let window = self.downgrade();
+            self.task_entry
+                .delegate()
+                .unwrap()
+                .connect_insert_text(move |editable, text, _| {
+                    if let Some(window) = window.upgrade()
+                        && window.paste_checklist(text)
+                    {
+                        editable.stop_signal_emission_by_name("insert-text");
+                    }
+                });
+        }
+        // F2 renames the focused task, Alt+Up and Alt+Down move it
+        {
+            let controller = ShortcutController::new();
+            controller.add_shortcut(Shortcut::new(
+                ShortcutTrigger::parse_string("F2"),
+                Some(CallbackAction::new(|list_view, _| {
+                    match focused_task_row(list_view) {
+                        Some(task_row) => {
+                            task_row.start_editing();
+                            glib::Propagation::Stop
+                        }
+                        None => glib::Propagation::Proceed,
+                    }
+                })),
+            ));
+            for (trigger, up) in [("<Alt>Up", true), ("<Alt>Down", false)] {
+                let window = self.downgrade();
+                controller.add_shortcut(Shortcut::new(
+                    ShortcutTrigger::parse_string(trigger),
+                    Some(CallbackAction::new(move |list_view, _| {
+                        match (
+                            window.upgrade(),
+                            focused_task_row(list_view).and_then(|r| r.task_object()),
+                        ) {
+                            (Some(window), Some(task_object)) => {
+                                window.move_task_by_step(task_object.get_id(), up);
+                                glib::Propagation::Stop
+                            }
+                            _ => glib::Propagation::Proceed,
+                        }
+                    })),
+                ));
+            }
+            self.task_list.add_controller(controller);
+        }
         // Initialize the filter
         let filter = self.task_filter.get_or_init(|| {
             let window = self.downgrade();
@@ -352,16 +1513,31 @@ impl ObjectImpl for MainWindowImp {
             });
         }
 
-        // Configure widget building for ListBox of collections
-        self.collection_list_box
-            .bind_model(Some(&self.collections), |collection_object| {
-                let collection_object = collection_object
-                    .downcast_ref::<CollectionObject>()
-                    .unwrap()
-                    .clone();
+        // Initialize the sorter, tasks that compare equal keep their position
+        let sorter = self.task_sorter.get_or_init(|| {
+            let window = self.downgrade();
+            CustomSorter::new(move |a, b| {
+                let a = a.downcast_ref::<TaskObject>().unwrap();
+                let b = b.downcast_ref::<TaskObject>().unwrap();
+                let window = window.upgrade().unwrap();
+                let sort_mode: SortMode = window.sort_mode.borrow().as_str().into();
 
-                CollectionRow::new(&collection_object).upcast::<Widget>()
+                match sort_mode {
+                    SortMode::Position => gtk::Ordering::Equal,
+                    SortMode::Priority => b.priority().cmp(&a.priority()).into(),
+                    SortMode::Created => b.created_at().cmp(&a.created_at()).into(),
+                    SortMode::Modified => b.modified_at().cmp(&a.modified_at()).into(),
+                    SortMode::Completed => b.completed_at().cmp(&a.completed_at()).into(),
+                }
+            })
+        });
+        {
+            let sorter = ObjectExt::downgrade(sorter);
+            self.obj().connect_sort_mode_notify(move |_| {
+                let sorter = sorter.upgrade().unwrap();
+                sorter.changed(gtk::SorterChange::Different);
             });
+        }
 
         // Watch the selected collection for changes
         {
@@ -370,54 +1546,61 @@ impl ObjectImpl for MainWindowImp {
                 .borrow_mut()
                 .watch(move |collection_object| {
                     let window = window.upgrade().unwrap();
+                    if let Some((c, handler)) = window.selected_collection_handler.take() {
+                        c.disconnect(handler);
+                    }
+                    if let Some(binding) = window.selected_collection_title.take() {
+                        binding.unbind();
+                    }
                     if let Some(c) = collection_object {
-                        window.task_model.set_model(Some(&FilterListModel::new(
-                            Some(c.tasks()),
-                            Some(window.task_filter.get().unwrap().clone()),
-                        )));
+                        // Keep the order up to date when a sorted property of a task changes
+                        let sorter = ObjectExt::downgrade(window.task_sorter.get().unwrap());
+                        let handler = c.connect_task_notify(move |_, _, property| {
+                            if let Some(sorter) = sorter.upgrade()
+                                && ["priority", "modified-at", "completed-at"].contains(&property)
+                            {
+                                sorter.changed(gtk::SorterChange::Different);
+                            }
+                        });
+                        window
+                            .selected_collection_handler
+                            .replace(Some((c.clone(), handler)));
+
                         window
-                            .obj()
-                            .set_task_page_title(format!("Tasks of {}", c.title()));
+                            .task_model
+                            .set_model(Some(&window.task_tree(&c.tasks())));
+                        window.selected_collection_title.replace(Some(
+                            c.bind_property("title", &*window.obj(), "task-page-title")
+                                .transform_to(|_, title: &str| Some(format!("Tasks of {title}")))
+                                .sync_create()
+                                .build(),
+                        ));
                     } else {
-                        window.task_model.set_model(None::<&FilterListModel>);
+                        window.task_model.set_model(None::<&TreeListModel>);
                         window.obj().set_task_page_title("Tasks");
                     }
                 });
         }
 
-        // Handle the outer stack page switching
-        let stack = self.stack.clone();
-        self.collections
-            .connect_items_changed(move |collections, _, _, _| {
-                stack.set_visible_child_name(if collections.n_items() == 0 {
-                    "placeholder"
-                } else {
-                    "main"
-                })
-            });
-
-        // Restore state from filesystem
-        let mut path = data_path();
-
-        let result = (|| -> Result<(), Box<dyn Error>> {
-            fs::create_dir_all(&path)?;
-            path.push("collections.json");
+        // Configure the tag list, which is hidden while no task has tags
+        self.tag_list_box.bind_model(Some(&self.tags), |tag| {
+            let tag = tag.downcast_ref::<StringObject>().unwrap().string();
 
-            let file = fs::OpenOptions::new()
-                .read(true)
-                .write(true)
-                .create(true)
-                .open(path)?;
-            let v: ListStoreSer<CollectionObject> = serde_json::from_reader(&file)?;
-
-            for c in v.extract().iter::<CollectionObject>().map(Result::unwrap) {
-                self.collections.append(&c);
-            }
-
-            Ok(())
-        })();
-        if let Err(err) = result {
-            eprintln!("Error occurred trying to load collections: {err}")
+            adw::ActionRow::builder()
+                .title(format!("#{tag}"))
+                .activatable(true)
+                .build()
+                .upcast::<Widget>()
+        });
+        for widget in [
+            self.tag_heading.upcast_ref::<Widget>(),
+            self.tag_list_box.upcast_ref(),
+        ] {
+            self.tags
+                .bind_property("n-items", widget, "visible")
+                .transform_to(|_, n_items: u32| Some(n_items != 0))
+                .sync_create()
+                .build();
         }
 
         // Store state to filesystem before exiting
@@ -427,6 +1610,24 @@ impl ObjectImpl for MainWindowImp {
             return glib::Propagation::Proceed;
         });
     }
+    fn dispose(&self) {
+        // The collections belong to the application and outlive the window
+        if let (Some(collections), Some(handler)) =
+            (self.collections.get(), self.collections_handler.take())
+        {
+            collections.disconnect(handler);
+        }
+        for (c, (tasks_changed, task_notify)) in self.watched_collections.take() {
+            c.disconnect(tasks_changed);
+            c.disconnect(task_notify);
+        }
+        if let Some((c, handler)) = self.selected_collection_handler.take() {
+            c.disconnect(handler);
+        }
+        if let Some(binding) = self.selected_collection_title.take() {
+            binding.unbind();
+        }
+    }
 }
 impl WidgetImpl for MainWindowImp {}
 impl WindowImpl for MainWindowImp {}
@@ -0,0 +1,23 @@
+use adw::Dialog;
+use adw::subclass::prelude::*;
+use gtk::glib::{self, Object};
+
+use crate::{main_window::MainWindow, task_object::TaskObject};
+
+glib::wrapper! {
+    pub struct TaskDetail(ObjectSubclass<task_detail_imp::TaskDetailImp>)
+        @extends Dialog, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::ShortcutManager;
+}
+
+impl TaskDetail {
+    pub fn new(task_object: &TaskObject, window: &MainWindow) -> Self {
+        let self_: Self = Object::builder().build();
+
+        self_.imp().bind(task_object, window);
+
+        self_
+    }
+}
+
+mod task_detail_imp;
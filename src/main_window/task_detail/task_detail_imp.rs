@@ -0,0 +1,498 @@
+use std::cell::{Cell, RefCell};
+
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use adw::{ActionRow, ComboRow, Dialog, EntryRow, ExpanderRow, SpinRow, SwitchRow};
+use glib::subclass::InitializingObject;
+use gtk::Box as GtkBox;
+use gtk::Calendar;
+use gtk::CheckButton;
+use gtk::CompositeTemplate;
+use gtk::EventControllerFocus;
+use gtk::ListBox;
+use gtk::TextView;
+use gtk::ToggleButton;
+use gtk::Widget;
+use gtk::glib::{self, Binding, DateTime, SignalHandlerId, TimeZone, Variant, WeakRef};
+
+use crate::due_date::DueDate;
+use crate::main_window::MainWindow;
+use crate::priority::Priority;
+use crate::recurrence::Recurrence;
+use crate::task_object::{self, TaskObject};
+
+#[derive(CompositeTemplate, Default)]
+#[template(resource = "/me/gtk-rs-test/test/task_detail.ui")]
+pub struct TaskDetailImp {
+    #[template_child]
+    name_row: TemplateChild<EntryRow>,
+    #[template_child]
+    checked_row: TemplateChild<SwitchRow>,
+    #[template_child]
+    priority_row: TemplateChild<ComboRow>,
+    #[template_child]
+    tags_row: TemplateChild<EntryRow>,
+    #[template_child]
+    due_row: TemplateChild<ExpanderRow>,
+    #[template_child]
+    due_calendar: TemplateChild<Calendar>,
+    #[template_child]
+    due_time_row: TemplateChild<SwitchRow>,
+    #[template_child]
+    due_hour_row: TemplateChild<SpinRow>,
+    #[template_child]
+    due_minute_row: TemplateChild<SpinRow>,
+    #[template_child]
+    repeat_row: TemplateChild<ComboRow>,
+    #[template_child]
+    weekdays_row: TemplateChild<ActionRow>,
+    #[template_child]
+    weekdays_box: TemplateChild<GtkBox>,
+    #[template_child]
+    repeat_days_row: TemplateChild<SpinRow>,
+    #[template_child]
+    derive_checked_row: TemplateChild<SwitchRow>,
+    #[template_child]
+    new_subtask_row: TemplateChild<EntryRow>,
+    #[template_child]
+    subtasks_list: TemplateChild<ListBox>,
+    #[template_child]
+    notes_view: TemplateChild<TextView>,
+    #[template_child]
+    created_row: TemplateChild<ActionRow>,
+    #[template_child]
+    modified_row: TemplateChild<ActionRow>,
+    #[template_child]
+    completed_row: TemplateChild<ActionRow>,
+
+    task_object: RefCell<Option<TaskObject>>,
+    bindings: RefCell<Vec<Binding>>,
+    due_handler: RefCell<Option<SignalHandlerId>>,
+    /// Set while the due date widgets are filled from the task, to avoid writing it back
+    updating_due: Cell<bool>,
+    /// Toggles for Monday to Sunday
+    weekday_buttons: RefCell<Vec<ToggleButton>>,
+    /// Set while the recurrence widgets are filled from the task
+    updating_recurrence: Cell<bool>,
+    /// Window the task is changed through
+    window: WeakRef<MainWindow>,
+}
+
+#[gtk::template_callbacks]
+impl TaskDetailImp {
+    #[template_callback]
+    fn handle_name_row_applied(&self) {
+        let name = self.name_row.text();
+        let name = name.trim();
+        if name.is_empty() {
+            return;
+        }
+
+        self.edit("win.rename-task", name.to_variant());
+    }
+    #[template_callback]
+    fn handle_checked_row_changed(&self) {
+        if self
+            .task_object
+            .borrow()
+            .as_ref()
+            .is_some_and(|t| t.checked() != self.checked_row.is_active())
+        {
+            self.edit("win.check-task", self.checked_row.is_active().to_variant());
+        }
+    }
+    #[template_callback]
+    fn handle_recurrence_changed(&self) {
+        self.update_recurrence_rows();
+        if self.updating_recurrence.get() {
+            return;
+        }
+
+        let recurrence = self
+            .recurrence()
+            .map(|r| serde_json::to_string(&r).unwrap())
+            .unwrap_or_default();
+        self.edit("win.set-task-recurrence", recurrence.to_variant());
+    }
+    #[template_callback]
+    fn handle_tags_row_applied(&self) {
+        let tags = task_object::parse_tags(&self.tags_row.text());
+
+        self.edit("win.set-task-tags", tags.to_variant());
+    }
+    #[template_callback]
+    fn handle_new_subtask_activated(&self) {
+        let name = self.new_subtask_row.text();
+        let name = name.trim();
+        if name.is_empty() {
+            return;
+        }
+
+        self.edit("win.add-subtask", name.to_variant());
+        self.new_subtask_row.set_text("");
+    }
+    #[template_callback]
+    fn handle_due_changed(&self) {
+        if self.updating_due.get() {
+            return;
+        }
+
+        let due = self.due().map(|due| due.to_string()).unwrap_or_default();
+        self.edit("win.set-task-due", due.to_variant());
+    }
+    fn handle_priority_changed(&self) {
+        if let Some(priority) = Priority::ALL.get(self.priority_row.selected() as usize) {
+            self.edit(
+                "win.set-task-priority",
+                Into::<&str>::into(*priority).to_variant(),
+            );
+        }
+    }
+    fn handle_derive_checked_changed(&self) {
+        self.edit(
+            "win.set-task-derive-checked",
+            self.derive_checked_row.is_active().to_variant(),
+        );
+    }
+    /// Notes are taken over once the text view loses focus or the dialog is closed,
+    /// instead of on every key press
+    fn commit_notes(&self) {
+        let buffer = self.notes_view.buffer();
+        let notes = buffer.text(&buffer.start_iter(), &buffer.end_iter(), false);
+        self.edit("win.set-task-notes", notes.to_variant());
+    }
+
+    /// Change the task through the window, so that it can be undone
+    ///
+    /// `value` is passed to `action` after the id of the task. Unchanged values are
+    /// ignored by the window.
+    fn edit(&self, action: &str, value: Variant) {
+        let Some(id) = self.task_object.borrow().as_ref().map(TaskObject::get_id) else {
+            return;
+        };
+        // Activated on the window, as the dialog may already be detached when closed
+        let Some(window) = self.window.upgrade() else {
+            return;
+        };
+
+        let parameter = Variant::tuple_from_iter([id.to_variant(), value]);
+        WidgetExt::activate_action(&window, action, Some(&parameter)).unwrap();
+    }
+
+    pub(super) fn bind(&self, task_object: &TaskObject, window: &MainWindow) {
+        self.window.set(Some(window));
+        *self.task_object.borrow_mut() = Some(task_object.clone());
+
+        self.name_row.set_text(&task_object.name());
+        self.tags_row.set_text(
+            &task_object
+                .tags()
+                .iter()
+                .map(|tag| format!("#{tag}"))
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+        self.update_due(task_object.due().as_ref());
+        self.fill_recurrence(task_object.recurrence().as_ref());
+
+        let mut bindings = self.bindings.borrow_mut();
+        bindings.push(
+            task_object
+                .bind_property("checked", &*self.checked_row, "active")
+                .sync_create()
+                .build(),
+        );
+        bindings.push(
+            task_object
+                .bind_property("priority", &*self.priority_row, "selected")
+                .transform_to(|_, priority: Priority| {
+                    Priority::ALL
+                        .iter()
+                        .position(|p| *p == priority)
+                        .map(|i| i as u32)
+                })
+                .sync_create()
+                .build(),
+        );
+        bindings.push(
+            task_object
+                .bind_property("derive-checked", &*self.derive_checked_row, "active")
+                .sync_create()
+                .build(),
+        );
+        bindings.push(
+            task_object
+                .subtasks()
+                .bind_property("n-items", &*self.subtasks_list, "visible")
+                .transform_to(|_, n_items: u32| Some(n_items != 0))
+                .sync_create()
+                .build(),
+        );
+        bindings.push(
+            task_object
+                .bind_property("notes", &self.notes_view.buffer(), "text")
+                .sync_create()
+                .build(),
+        );
+        for (property, row) in [
+            ("created-at", &*self.created_row),
+            ("modified-at", &*self.modified_row),
+            ("completed-at", &*self.completed_row),
+        ] {
+            bindings.push(
+                task_object
+                    .bind_property(property, row, "subtitle")
+                    .transform_to(|_, date_time: Option<DateTime>| {
+                        Some(date_time.as_ref().map(timestamp_label).unwrap_or_default())
+                    })
+                    .sync_create()
+                    .build(),
+            );
+        }
+        bindings.push(
+            task_object
+                .bind_property("completed-at", &*self.completed_row, "visible")
+                .transform_to(|_, date_time: Option<DateTime>| Some(date_time.is_some()))
+                .sync_create()
+                .build(),
+        );
+
+        self.subtasks_list
+            .bind_model(Some(&task_object.subtasks()), |subtask| {
+                let subtask = subtask.downcast_ref::<TaskObject>().unwrap();
+
+                let check_button = CheckButton::new();
+                subtask
+                    .bind_property("checked", &check_button, "active")
+                    .sync_create()
+                    .build();
+                let subtask_weak = subtask.downgrade();
+                check_button.connect_toggled(move |check_button| {
+                    if let Some(subtask) = subtask_weak.upgrade()
+                        && subtask.checked() != check_button.is_active()
+                    {
+                        check_button
+                            .activate_action(
+                                "win.check-task",
+                                Some(&(subtask.get_id(), check_button.is_active()).to_variant()),
+                            )
+                            .unwrap();
+                    }
+                });
+                let row = ActionRow::new();
+                row.add_prefix(&check_button);
+                row.set_activatable_widget(Some(&check_button));
+                subtask
+                    .bind_property("name", &row, "title")
+                    .sync_create()
+                    .build();
+
+                row.upcast::<Widget>()
+            });
+
+        // Keep the due date widgets in sync when the due date is changed elsewhere
+        let dialog = self.downgrade();
+        *self.due_handler.borrow_mut() = Some(task_object.connect_due_notify(move |task_object| {
+            if let Some(dialog) = dialog.upgrade() {
+                dialog.update_due(task_object.due().as_ref());
+            }
+        }));
+    }
+
+    /// Fill the recurrence widgets from `recurrence`
+    fn fill_recurrence(&self, recurrence: Option<&Recurrence>) {
+        self.updating_recurrence.set(true);
+
+        self.repeat_row.set_selected(match recurrence {
+            None => 0,
+            Some(Recurrence::Daily) => 1,
+            Some(Recurrence::Weekly { .. }) => 2,
+            Some(Recurrence::Monthly { .. }) => 3,
+            Some(Recurrence::AfterCompletion { .. }) => 4,
+        });
+        let weekdays = match recurrence {
+            Some(Recurrence::Weekly { weekdays }) => weekdays.clone(),
+            _ => Vec::new(),
+        };
+        for (day, button) in (1..).zip(self.weekday_buttons.borrow().iter()) {
+            button.set_active(weekdays.contains(&day));
+        }
+        if let Some(Recurrence::AfterCompletion { days }) = recurrence {
+            self.repeat_days_row.set_value(*days as f64);
+        }
+        self.update_recurrence_rows();
+
+        self.updating_recurrence.set(false);
+    }
+
+    /// Only show the rows relevant to the selected kind of recurrence
+    fn update_recurrence_rows(&self) {
+        self.weekdays_row
+            .set_visible(self.repeat_row.selected() == 2);
+        self.repeat_days_row
+            .set_visible(self.repeat_row.selected() == 4);
+    }
+
+    /// The recurrence currently described by the widgets
+    fn recurrence(&self) -> Option<Recurrence> {
+        match self.repeat_row.selected() {
+            1 => Some(Recurrence::Daily),
+            2 => Some(Recurrence::Weekly {
+                weekdays: (1..)
+                    .zip(self.weekday_buttons.borrow().iter())
+                    .filter(|(_, button)| button.is_active())
+                    .map(|(day, _)| day)
+                    .collect(),
+            }),
+            3 => Some(Recurrence::Monthly {
+                day: match self
+                    .task_object
+                    .borrow()
+                    .as_ref()
+                    .and_then(TaskObject::recurrence)
+                {
+                    Some(Recurrence::Monthly { day }) => day,
+                    _ => None,
+                },
+            }),
+            4 => Some(Recurrence::AfterCompletion {
+                days: self.repeat_days_row.value() as u32,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Fill the due date widgets from `due`
+    fn update_due(&self, due: Option<&DueDate>) {
+        if due == self.due().as_ref() {
+            return;
+        }
+
+        self.updating_due.set(true);
+
+        self.due_row.set_enable_expansion(due.is_some());
+        if let Some(due) = due {
+            let date_time = due
+                .date_time()
+                .to_local()
+                .unwrap_or(due.date_time().clone());
+            self.due_calendar.select_day(&date_time);
+            self.due_time_row.set_active(due.has_time());
+            if due.has_time() {
+                self.due_hour_row.set_value(date_time.hour().into());
+                self.due_minute_row.set_value(date_time.minute().into());
+            }
+        }
+
+        self.updating_due.set(false);
+    }
+
+    /// The due date currently described by the widgets
+    fn due(&self) -> Option<DueDate> {
+        if !self.due_row.enables_expansion() {
+            return None;
+        }
+
+        let date = self.due_calendar.date();
+        let (year, month, day) = date.ymd();
+        if !self.due_time_row.is_active() {
+            return DueDate::from_ymd(year, month, day);
+        }
+
+        DateTime::new(
+            &TimeZone::local(),
+            year,
+            month,
+            day,
+            self.due_hour_row.value() as i32,
+            self.due_minute_row.value() as i32,
+            0.0,
+        )
+        .ok()
+        .map(DueDate::from_date_time)
+    }
+}
+
+/// Point in time in the local timezone, e.g. "14 Mar 2025, 17:30"
+fn timestamp_label(date_time: &DateTime) -> String {
+    date_time
+        .to_local()
+        .and_then(|d| d.format("%e %b %Y, %H:%M"))
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default()
+}
+
+#[glib::object_subclass]
+impl ObjectSubclass for TaskDetailImp {
+    const NAME: &'static str = "GtkRsTestTaskDetail";
+    type Type = super::TaskDetail;
+    type ParentType = Dialog;
+
+    fn class_init(klass: &mut Self::Class) {
+        klass.bind_template();
+        klass.bind_template_callbacks();
+    }
+
+    fn instance_init(obj: &InitializingObject<Self>) {
+        obj.init_template();
+    }
+}
+
+impl ObjectImpl for TaskDetailImp {
+    fn constructed(&self) {
+        self.parent_constructed();
+
+        let mut weekday_buttons = self.weekday_buttons.borrow_mut();
+        for name in ["M", "T", "W", "T", "F", "S", "S"] {
+            let button = ToggleButton::with_label(name);
+            let dialog = self.downgrade();
+            button.connect_toggled(move |_| {
+                if let Some(dialog) = dialog.upgrade() {
+                    dialog.handle_recurrence_changed();
+                }
+            });
+            self.weekdays_box.append(&button);
+            weekday_buttons.push(button);
+        }
+
+        // Changes of the task are recorded by the window, so that they can be undone
+        let dialog = self.downgrade();
+        self.priority_row.connect_selected_notify(move |_| {
+            if let Some(dialog) = dialog.upgrade() {
+                dialog.handle_priority_changed();
+            }
+        });
+        let dialog = self.downgrade();
+        self.derive_checked_row.connect_active_notify(move |_| {
+            if let Some(dialog) = dialog.upgrade() {
+                dialog.handle_derive_checked_changed();
+            }
+        });
+        let focus_controller = EventControllerFocus::new();
+        let dialog = self.downgrade();
+        focus_controller.connect_leave(move |_| {
+            if let Some(dialog) = dialog.upgrade() {
+                dialog.commit_notes();
+            }
+        });
+        self.notes_view.add_controller(focus_controller);
+    }
+    fn dispose(&self) {
+        self.bindings
+            .borrow_mut()
+            .drain(..)
+            .for_each(|b| b.unbind());
+        if let (Some(task_object), Some(handler)) =
+            (self.task_object.take(), self.due_handler.take())
+        {
+            task_object.disconnect(handler);
+        }
+    }
+}
+impl WidgetImpl for TaskDetailImp {}
+impl AdwDialogImpl for TaskDetailImp {
+    fn closed(&self) {
+        self.commit_notes();
+        self.parent_closed();
+    }
+}
@@ -1,11 +1,11 @@
 use adw::subclass::prelude::*;
 use gtk::{
     gio::{ActionEntry, SimpleActionGroup, prelude::ActionMapExtManual},
-    glib::{self, Object, clone::Downgrade},
+    glib::{self, Object, clone::Downgrade, prelude::StaticVariantType},
     prelude::WidgetExt,
 };
 
-use crate::task_object::TaskObject;
+use crate::{priority::Priority, task_object::TaskObject};
 
 glib::wrapper! {
     pub struct TaskRow(ObjectSubclass<task_row_imp::TaskRowImp>)
@@ -23,20 +23,69 @@ impl TaskRow {
         self.insert_action_group("row", Some(&group));
 
         let self0 = self.downgrade();
-        group.add_action_entries([ActionEntry::builder("copy")
-            .activate(move |_, _, _| {
-                self0.upgrade().unwrap().imp().copy();
-            })
-            .build()]);
+        let self1 = self.downgrade();
+        let self2 = self.downgrade();
+        let self3 = self.downgrade();
+        let self4 = self.downgrade();
+        group.add_action_entries([
+            ActionEntry::builder("copy")
+                .activate(move |_, _, _| {
+                    self0.upgrade().unwrap().imp().copy();
+                })
+                .build(),
+            ActionEntry::builder("rename")
+                .activate(move |_, _, _| {
+                    // Wait for the menu to close, which moves the focus back
+                    let self4 = self4.clone();
+                    glib::idle_add_local_once(move || {
+                        if let Some(task_row) = self4.upgrade() {
+                            task_row.start_editing();
+                        }
+                    });
+                })
+                .build(),
+            ActionEntry::builder("due-in-days")
+                .parameter_type(Some(&i32::static_variant_type()))
+                .activate(move |_, _, days| {
+                    let days = days.and_then(|d| d.get::<i32>()).unwrap();
+                    self1.upgrade().unwrap().imp().set_due_in_days(Some(days));
+                })
+                .build(),
+            ActionEntry::builder("clear-due")
+                .activate(move |_, _, _| {
+                    self2.upgrade().unwrap().imp().set_due_in_days(None);
+                })
+                .build(),
+            ActionEntry::builder("priority")
+                .parameter_type(Some(&String::static_variant_type()))
+                .activate(move |_, _, priority| {
+                    let priority = priority.and_then(|p| p.get::<String>()).unwrap();
+                    self3
+                        .upgrade()
+                        .unwrap()
+                        .imp()
+                        .set_priority(Priority::from(priority.as_str()));
+                })
+                .build(),
+        ]);
     }
 
-    pub(super) fn bind(&self, task_object: &TaskObject) {
-        self.imp().bind(task_object);
+    pub(super) fn bind(&self, task_object: &TaskObject, list_row: &gtk::TreeListRow) {
+        self.imp().bind(task_object, list_row);
     }
 
     pub(super) fn unbind(&self) {
         self.imp().unbind();
     }
+
+    pub(super) fn task_object(&self) -> Option<TaskObject> {
+        self.imp().task_object()
+    }
+
+    /// Turn the title into an entry for renaming the task
+    pub(super) fn start_editing(&self) {
+        self.imp().start_editing();
+    }
 }
 
 mod task_row_imp;
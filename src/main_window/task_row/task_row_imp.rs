@@ -1,13 +1,29 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 
 use adw::ActionRow;
 use adw::prelude::*;
 use adw::subclass::prelude::*;
+use gtk::Box as GtkBox;
 use gtk::CheckButton;
 use gtk::CompositeTemplate;
+use gtk::DragSource;
+use gtk::DropTarget;
+use gtk::Entry;
+use gtk::EventControllerFocus;
+use gtk::EventControllerKey;
 use gtk::GestureClick;
+use gtk::Label;
+use gtk::PickFlags;
 use gtk::PopoverMenu;
+use gtk::TreeExpander;
+use gtk::TreeListRow;
+use gtk::Widget;
+use gtk::WidgetPaintable;
+use gtk::gdk::BUTTON_PRIMARY;
 use gtk::gdk::BUTTON_SECONDARY;
+use gtk::gdk::ContentProvider;
+use gtk::gdk::DragAction;
+use gtk::gdk::Key;
 use gtk::gdk::Rectangle;
 use gtk::gio::Menu;
 use gtk::gio::MenuItem;
@@ -18,15 +34,30 @@ use gtk::glib::object::CastNone;
 use gtk::glib::object::ObjectExt;
 use gtk::glib::subclass::InitializingObject;
 
-use crate::task_object::TaskObject;
+use crate::due_date::DueDate;
+use crate::priority::Priority;
+use crate::task_object::{self, TaskObject};
+
+/// Id of a dragged task, a type of its own so that rows only accept dropped tasks
+#[derive(Clone, glib::Boxed)]
+#[boxed_type(name = "GtkRsTestDraggedTask")]
+struct DraggedTask(task_object::IdType);
 
 #[derive(CompositeTemplate, Properties)]
 #[properties(wrapper_type = super::TaskRow)]
 #[template(resource = "/me/gtk-rs-test/test/task_row.ui")]
 pub struct TaskRowImp {
+    #[template_child]
+    expander: TemplateChild<TreeExpander>,
     #[template_child]
     check_button: TemplateChild<CheckButton>,
     #[template_child]
+    name_entry: TemplateChild<Entry>,
+    #[template_child]
+    tags_box: TemplateChild<GtkBox>,
+    #[template_child]
+    priority_label: TemplateChild<Label>,
+    #[template_child]
     rightclick_menu: TemplateChild<PopoverMenu>,
     #[template_child]
     rightclick_menu_model: TemplateChild<Menu>,
@@ -35,35 +66,54 @@ pub struct TaskRowImp {
     title: RefCell<String>,
 
     name: RefCell<String>,
+    task_object: RefCell<Option<TaskObject>>,
     bindings: RefCell<Vec<Binding>>,
     delete_menu_item: MenuItem,
+    /// Whether `name_entry` is shown in place of the title
+    editing: Cell<bool>,
 }
 
 impl Default for TaskRowImp {
     fn default() -> Self {
         Self {
+            expander: Default::default(),
             check_button: Default::default(),
+            name_entry: Default::default(),
+            tags_box: Default::default(),
+            priority_label: Default::default(),
             rightclick_menu: Default::default(),
             rightclick_menu_model: Default::default(),
             title: Default::default(),
             name: Default::default(),
+            task_object: Default::default(),
             bindings: Default::default(),
             delete_menu_item: MenuItem::new(Some("Delete"), None),
+            editing: Default::default(),
         }
     }
 }
 
 impl TaskRowImp {
-    pub fn bind(&self, task_object: &TaskObject) {
+    pub fn bind(&self, task_object: &TaskObject, list_row: &TreeListRow) {
         let mut bindings = self.bindings.borrow_mut();
 
+        self.expander.set_list_row(Some(list_row));
+        bindings.push(
+            task_object
+                .subtasks()
+                .bind_property("n-items", &*self.expander, "hide-expander")
+                .transform_to(|_, n_items: u32| Some(n_items == 0))
+                .sync_create()
+                .build(),
+        );
+
+        *self.task_object.borrow_mut() = Some(task_object.clone());
         self.update_menu_item(task_object);
 
         bindings.push(
             task_object
                 .bind_property("checked", &*self.check_button, "active")
                 .sync_create()
-                .bidirectional()
                 .build(),
         );
         bindings.push(
@@ -74,24 +124,258 @@ impl TaskRowImp {
                     let self_ = self_.imp();
 
                     *self_.name.borrow_mut() = name.to_string();
+                    // The title is restored when editing stops
+                    if self_.editing.get() {
+                        return None;
+                    }
 
                     Some(self_.title(None, Some(name)))
                 })
                 .sync_create()
                 .build(),
         );
+        for property in ["due", "recurrence"] {
+            bindings.push(
+                task_object
+                    .bind_property(property, &*self.obj(), "subtitle")
+                    .transform_to_with_values(|binding, _| {
+                        let task_object = binding.source().and_downcast::<TaskObject>().unwrap();
+                        let self_ = binding.target().and_downcast::<super::TaskRow>().unwrap();
+                        self_.imp().update_overdue(task_object.due().as_ref());
+                        if self_.imp().editing.get() {
+                            return None;
+                        }
+
+                        Some(subtitle(&task_object).to_value())
+                    })
+                    .sync_create()
+                    .build(),
+            );
+        }
+        bindings.push(
+            task_object
+                .bind_property("tags", &*self.tags_box, "visible")
+                .transform_to(|binding, tags: Vec<String>| {
+                    let tags_box = binding.target().and_downcast::<GtkBox>().unwrap();
+                    while let Some(child) = tags_box.first_child() {
+                        tags_box.remove(&child);
+                    }
+                    for tag in &tags {
+                        let label = Label::new(Some(&format!("#{tag}")));
+                        label.add_css_class("tag");
+                        tags_box.append(&label);
+                    }
+
+                    Some(!tags.is_empty())
+                })
+                .sync_create()
+                .build(),
+        );
+        bindings.push(
+            task_object
+                .bind_property("priority", &*self.priority_label, "label")
+                .transform_to(|binding, priority: Priority| {
+                    let label = binding.target().and_downcast::<Label>().unwrap();
+                    for p in Priority::ALL {
+                        label.remove_css_class(p.into());
+                    }
+                    label.add_css_class(priority.into());
+                    label.set_visible(priority != Priority::None);
+
+                    Some(priority.label())
+                })
+                .sync_create()
+                .build(),
+        );
     }
     pub fn unbind(&self) {
+        self.stop_editing(false);
         self.bindings
             .borrow_mut()
             .drain(..)
             .for_each(|b| b.unbind());
+        self.expander.set_list_row(None);
+        *self.task_object.borrow_mut() = None;
+    }
+
+    pub(super) fn task_object(&self) -> Option<TaskObject> {
+        self.task_object.borrow().clone()
+    }
+
+    /// Highlight the row when the task is unresolved and past its due date
+    fn update_overdue(&self, due: Option<&DueDate>) {
+        let overdue = !self.check_button.is_active() && due.is_some_and(DueDate::is_overdue);
+
+        if overdue {
+            self.obj().add_css_class("overdue");
+        } else {
+            self.obj().remove_css_class("overdue");
+        }
+    }
+
+    /// Refresh the due date label, which is relative to the current day
+    pub(super) fn refresh_due(&self) {
+        let task_object = match &*self.task_object.borrow() {
+            Some(t) => t.clone(),
+            None => return,
+        };
+
+        self.update_overdue(task_object.due().as_ref());
+        if !self.editing.get() {
+            self.obj().set_subtitle(&subtitle(&task_object));
+        }
+    }
+
+    /// Change the priority through the window, so that it can be undone
+    pub(super) fn set_priority(&self, priority: Priority) {
+        // Not borrowed while the change may rebind the row
+        let task_object = self.task_object.borrow().clone();
+        if let Some(task_object) = task_object {
+            self.obj()
+                .activate_action(
+                    "win.set-task-priority",
+                    Some(&(task_object.get_id(), Into::<&str>::into(priority)).to_variant()),
+                )
+                .unwrap();
+        }
+    }
+
+    /// Change the due date through the window, so that it can be undone
+    pub(super) fn set_due_in_days(&self, days: Option<i32>) {
+        let task_object = self.task_object.borrow().clone();
+        if let Some(task_object) = task_object {
+            let due = days
+                .and_then(DueDate::in_days)
+                .map(|due| due.to_string())
+                .unwrap_or_default();
+            self.obj()
+                .activate_action(
+                    "win.set-task-due",
+                    Some(&(task_object.get_id(), due).to_variant()),
+                )
+                .unwrap();
+        }
+    }
+
+    /// Show an entry for the name of the task in place of the title
+    pub(super) fn start_editing(&self) {
+        let Some(task_object) = self.task_object.borrow().clone() else {
+            return;
+        };
+        if self.editing.replace(true) {
+            return;
+        }
+
+        // The title box is hidden while both title and subtitle are empty
+        self.obj().set_title("");
+        self.obj().set_subtitle("");
+        self.name_entry.set_text(&task_object.name());
+        self.name_entry.set_visible(true);
+        // Descendants of a row that can't be focused can't be focused either
+        self.obj().set_can_focus(true);
+        self.name_entry.grab_focus();
+    }
+
+    /// Whether `x`, `y` lies on the title of the row rather than on a widget next to it
+    fn on_title(&self, x: f64, y: f64) -> bool {
+        let Some(picked) = self.obj().pick(x, y, PickFlags::DEFAULT) else {
+            return false;
+        };
+        let widgets: [&Widget; 4] = [
+            self.expander.upcast_ref(),
+            self.check_button.upcast_ref(),
+            self.tags_box.upcast_ref(),
+            self.priority_label.upcast_ref(),
+        ];
+
+        !widgets
+            .iter()
+            .any(|widget| picked == **widget || picked.is_ancestor(*widget))
+    }
+
+    /// Hide the entry for the name again, renaming the task if `commit` is set
+    fn stop_editing(&self, commit: bool) {
+        if !self.editing.replace(false) {
+            return;
+        }
+        let task_object = self.task_object.borrow().clone();
+
+        let name = self.name_entry.text();
+        let name = name.trim();
+        if commit
+            && !name.is_empty()
+            && let Some(task_object) = &task_object
+            && task_object.name() != name
+        {
+            self.obj()
+                .activate_action(
+                    "win.rename-task",
+                    Some(&(task_object.get_id(), name).to_variant()),
+                )
+                .unwrap();
+        }
+
+        self.name_entry.set_visible(false);
+        self.obj().set_can_focus(false);
+        self.obj().set_title(self.title(None, None));
+        if let Some(task_object) = &task_object {
+            self.obj().set_subtitle(&subtitle(task_object));
+        }
+    }
+
+    /// Check the task through the window, so that it can be undone
+    fn handle_check_button_toggled(&self) {
+        let task_object = match &*self.task_object.borrow() {
+            Some(t) => t.clone(),
+            None => return,
+        };
+        let active = self.check_button.is_active();
+        if active == task_object.checked() {
+            return;
+        }
+
+        self.obj()
+            .activate_action(
+                "win.check-task",
+                Some(&(task_object.get_id(), active).to_variant()),
+            )
+            .unwrap();
     }
 
     fn update_menu_item(self: &Self, task_object: &TaskObject) {
         self.rightclick_menu_model.remove_all();
         self.rightclick_menu_model
             .append(Some("Copy"), Some("row.copy"));
+        self.rightclick_menu_model
+            .append(Some("Rename"), Some("row.rename"));
+
+        let details_item = MenuItem::new(Some("Details…"), None);
+        details_item.set_action_and_target_value(
+            Some("win.show-task-details"),
+            Some(&task_object.get_id().to_variant()),
+        );
+        self.rightclick_menu_model.append_item(&details_item);
+
+        let due_menu = Menu::new();
+        due_menu.append(Some("Today"), Some("row.due-in-days(0)"));
+        due_menu.append(Some("Tomorrow"), Some("row.due-in-days(1)"));
+        due_menu.append(Some("Next Week"), Some("row.due-in-days(7)"));
+        due_menu.append(Some("No Due Date"), Some("row.clear-due"));
+        self.rightclick_menu_model
+            .append_submenu(Some("Due Date"), &due_menu);
+
+        let priority_menu = Menu::new();
+        for priority in Priority::ALL.into_iter().rev() {
+            let item = MenuItem::new(Some(priority.label()), None);
+            item.set_action_and_target_value(
+                Some("row.priority"),
+                Some(&Into::<&str>::into(priority).to_variant()),
+            );
+            priority_menu.append_item(&item);
+        }
+        self.rightclick_menu_model
+            .append_submenu(Some("Priority"), &priority_menu);
+
         self.delete_menu_item.set_action_and_target_value(
             Some("win.remove-task"),
             Some(&task_object.get_id().to_variant()),
@@ -113,7 +397,8 @@ impl TaskRowImp {
     }
 
     pub(super) fn copy(&self) {
-        let content = self.obj().title();
+        // The title holds Pango markup, e.g. for the strikethrough of done tasks
+        let content = self.name.borrow().clone();
         self.obj().clipboard().set(&content);
 
         self.obj()
@@ -125,6 +410,18 @@ impl TaskRowImp {
     }
 }
 
+/// Due date and recurrence of the task, e.g. "Due tomorrow · Repeats daily"
+fn subtitle(task_object: &TaskObject) -> String {
+    [
+        task_object.due().map(|due| due.label()),
+        task_object.recurrence().map(|r| r.label()),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>()
+    .join(" · ")
+}
+
 #[glib::object_subclass]
 impl ObjectSubclass for TaskRowImp {
     const NAME: &'static str = "GtkRsTestTaskRow";
@@ -149,9 +446,135 @@ impl ObjectImpl for TaskRowImp {
                 let self_ = binding.target().and_downcast::<super::TaskRow>().unwrap();
                 let self_ = self_.imp();
 
+                self_.refresh_due();
+                if self_.editing.get() {
+                    return None;
+                }
+
                 Some(self_.title(Some(active), None))
             })
             .build();
+        {
+            let task_row = self.downgrade();
+            self.check_button.connect_toggled(move |_| {
+                if let Some(task_row) = task_row.upgrade() {
+                    task_row.handle_check_button_toggled();
+                }
+            });
+        }
+
+        // Relative due date labels and the overdue state change over time
+        {
+            let task_row = self.downgrade();
+            glib::timeout_add_seconds_local(60, move || match task_row.upgrade() {
+                Some(task_row) => {
+                    task_row.refresh_due();
+                    glib::ControlFlow::Continue
+                }
+                None => glib::ControlFlow::Break,
+            });
+        }
+
+        // Enter renames the task, Escape cancels, losing focus renames it as well
+        {
+            let task_row = self.downgrade();
+            self.name_entry.connect_activate(move |_| {
+                if let Some(task_row) = task_row.upgrade() {
+                    task_row.stop_editing(true);
+                }
+            });
+        }
+        let key_controller = EventControllerKey::new();
+        {
+            let task_row = self.downgrade();
+            key_controller.connect_key_pressed(move |_, key, _, _| match task_row.upgrade() {
+                Some(task_row) if key == Key::Escape => {
+                    task_row.stop_editing(false);
+                    glib::Propagation::Stop
+                }
+                _ => glib::Propagation::Proceed,
+            });
+        }
+        self.name_entry.add_controller(key_controller);
+        let focus_controller = EventControllerFocus::new();
+        {
+            let task_row = self.downgrade();
+            focus_controller.connect_leave(move |_| {
+                if let Some(task_row) = task_row.upgrade() {
+                    task_row.stop_editing(true);
+                }
+            });
+        }
+        self.name_entry.add_controller(focus_controller);
+
+        // Double-clicking the title renames the task, instead of activating the row
+        let double_click = GestureClick::builder().button(BUTTON_PRIMARY).build();
+        {
+            let task_row = self.downgrade();
+            double_click.connect_pressed(move |gesture, n_press, x, y| {
+                if n_press == 2
+                    && let Some(task_row) = task_row.upgrade()
+                    && !task_row.editing.get()
+                    && task_row.on_title(x, y)
+                {
+                    gesture.set_state(gtk::EventSequenceState::Claimed);
+                    task_row.start_editing();
+                }
+            });
+        }
+        self.obj().add_controller(double_click);
+
+        // Tasks are reordered by dragging them onto the upper or lower half of a sibling
+        let drag_source = DragSource::builder().actions(DragAction::MOVE).build();
+        {
+            let task_row = self.downgrade();
+            drag_source.connect_prepare(move |_, _, _| {
+                let task_row = task_row.upgrade()?;
+                if task_row.editing.get() {
+                    return None;
+                }
+                let task_object = task_row.task_object()?;
+
+                Some(ContentProvider::for_value(
+                    &DraggedTask(task_object.get_id()).to_value(),
+                ))
+            });
+        }
+        {
+            let task_row = self.downgrade();
+            drag_source.connect_drag_begin(move |drag_source, _| {
+                if let Some(task_row) = task_row.upgrade() {
+                    let paintable = WidgetPaintable::new(Some(&*task_row.obj()));
+                    drag_source.set_icon(Some(&paintable), 0, 0);
+                }
+            });
+        }
+        self.obj().add_controller(drag_source);
+
+        let drop_target = DropTarget::new(DraggedTask::static_type(), DragAction::MOVE);
+        {
+            let task_row = self.downgrade();
+            drop_target.connect_drop(move |_, value, _, y| {
+                let (Some(task_row), Ok(DraggedTask(id))) =
+                    (task_row.upgrade(), value.get::<DraggedTask>())
+                else {
+                    return false;
+                };
+                let Some(task_object) = task_row.task_object() else {
+                    return false;
+                };
+                let after = y > f64::from(task_row.obj().height()) / 2.0;
+
+                task_row
+                    .obj()
+                    .activate_action(
+                        "win.move-task",
+                        Some(&(id, task_object.get_id(), after).to_variant()),
+                    )
+                    .is_ok()
+            });
+        }
+        self.obj().add_controller(drop_target);
 
         self.rightclick_menu.set_parent(&*self.obj());
 
@@ -0,0 +1,259 @@
+use gtk::glib::{self, DateTime, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::due_date::DueDate;
+
+/// Rule describing when the next occurrence of a recurring task is due
+#[derive(Clone, Debug, PartialEq, glib::Boxed, Serialize, Deserialize)]
+#[boxed_type(name = "GtkRsTestRecurrence", nullable)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Recurrence {
+    Daily,
+    /// Weekdays are numbered from 1 (Monday) to 7 (Sunday)
+    Weekly {
+        weekdays: Vec<i32>,
+    },
+    /// Due on `day` of the month, or on the day of the month it was first due
+    Monthly {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        day: Option<i32>,
+    },
+    /// Due `days` days after the previous occurrence was completed
+    AfterCompletion {
+        days: u32,
+    },
+}
+
+impl Recurrence {
+    pub fn label(&self) -> String {
+        match self {
+            Recurrence::Daily => String::from("Repeats daily"),
+            Recurrence::Weekly { weekdays } if weekdays.is_empty() => {
+                String::from("Repeats weekly")
+            }
+            Recurrence::Weekly { weekdays } => {
+                const NAMES: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+                let mut weekdays = weekdays.clone();
+                weekdays.sort();
+                let names: Vec<&str> = weekdays
+                    .iter()
+                    .filter_map(|d| NAMES.get((*d - 1) as usize).copied())
+                    .collect();
+                format!("Repeats every {}", names.join(", "))
+            }
+            Recurrence::Monthly { .. } => String::from("Repeats monthly"),
+            Recurrence::AfterCompletion { days: 1 } => {
+                String::from("Repeats 1 day after completion")
+            }
+            Recurrence::AfterCompletion { days } => {
+                format!("Repeats {days} days after completion")
+            }
+        }
+    }
+
+    /// Rule of the occurrence following one due at `due`
+    ///
+    /// Monthly rules remember the day of the month they started on, so that a task due
+    /// on the 31st returns to it after shorter months.
+    pub fn following(&self, due: Option<&DueDate>) -> Recurrence {
+        match self {
+            Recurrence::Monthly { day: None } => Recurrence::Monthly {
+                day: due.map(|due| due.ymd().2),
+            },
+            _ => self.clone(),
+        }
+    }
+
+    /// Due date of the occurrence following one due at `due`, completed now
+    ///
+    /// Tasks without a due date are treated as due today.
+    pub fn next_due(&self, due: Option<&DueDate>) -> Option<DueDate> {
+        let today = DueDate::in_days(0)?;
+        let due = due.unwrap_or(&today);
+
+        let next = match self {
+            Recurrence::Daily => due.date_time().add_days(1).ok()?,
+            Recurrence::Weekly { weekdays } if weekdays.is_empty() => {
+                due.date_time().add_weeks(1).ok()?
+            }
+            Recurrence::Weekly { weekdays } => {
+                let mut next = due.date_time().add_days(1).ok()?;
+                for _ in 1..7 {
+                    if weekdays.contains(&next.day_of_week()) {
+                        break;
+                    }
+                    next = next.add_days(1).ok()?;
+                }
+                next
+            }
+            Recurrence::Monthly { day } => {
+                let time = due.date_time();
+                let (year, month, due_day) = time.ymd();
+                let (year, month) = if month == 12 {
+                    (year + 1, 1)
+                } else {
+                    (year, month + 1)
+                };
+                let day = day.unwrap_or(due_day).min(days_in_month(year, month));
+                DateTime::new(
+                    &time.timezone(),
+                    year,
+                    month,
+                    day,
+                    time.hour(),
+                    time.minute(),
+                    time.seconds(),
+                )
+                .ok()?
+            }
+            Recurrence::AfterCompletion { days } => {
+                let (year, month, day) = DateTime::now_local().ok()?.ymd();
+                let time = due.date_time();
+                DateTime::new(
+                    &time.timezone(),
+                    year,
+                    month,
+                    day,
+                    time.hour(),
+                    time.minute(),
+                    0.0,
+                )
+                .or_else(|_| DateTime::new(&TimeZone::local(), year, month, day, 0, 0, 0.0))
+                .ok()?
+                .add_days(*days as i32)
+                .ok()?
+            }
+        };
+
+        if due.has_time() {
+            Some(DueDate::from_date_time(next))
+        } else {
+            let (year, month, day) = next.ymd();
+            DueDate::from_ymd(year, month, day)
+        }
+    }
+}
+
+fn days_in_month(year: i32, month: i32) -> i32 {
+    match month {
+        4 | 6 | 9 | 11 => 30,
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        _ => 31,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: i32, day: i32) -> DueDate {
+        DueDate::from_ymd(year, month, day).unwrap()
+    }
+
+    fn timed(year: i32, month: i32, day: i32, hour: i32, minute: i32) -> DueDate {
+        DueDate::from_date_time(
+            DateTime::new(&TimeZone::local(), year, month, day, hour, minute, 0.0).unwrap(),
+        )
+    }
+
+    #[test]
+    fn daily_keeps_the_time() {
+        let daily = Recurrence::Daily;
+        assert_eq!(
+            daily.next_due(Some(&date(2025, 3, 14))),
+            Some(date(2025, 3, 15))
+        );
+        assert_eq!(
+            daily.next_due(Some(&timed(2025, 3, 14, 17, 30))),
+            Some(timed(2025, 3, 15, 17, 30))
+        );
+        assert_eq!(daily.next_due(None), DueDate::in_days(1));
+    }
+
+    #[test]
+    fn weekly_wraps_around_the_week() {
+        // 2025-03-14 is a Friday
+        let weekly = Recurrence::Weekly {
+            weekdays: vec![1, 3],
+        };
+        assert_eq!(
+            weekly.next_due(Some(&date(2025, 3, 14))),
+            Some(date(2025, 3, 17))
+        );
+        assert_eq!(
+            weekly.next_due(Some(&date(2025, 3, 17))),
+            Some(date(2025, 3, 19))
+        );
+
+        let wednesdays = Recurrence::Weekly { weekdays: vec![3] };
+        assert_eq!(
+            wednesdays.next_due(Some(&date(2025, 3, 19))),
+            Some(date(2025, 3, 26))
+        );
+
+        let weekly = Recurrence::Weekly {
+            weekdays: Vec::new(),
+        };
+        assert_eq!(
+            weekly.next_due(Some(&timed(2025, 3, 14, 9, 0))),
+            Some(timed(2025, 3, 21, 9, 0))
+        );
+    }
+
+    #[test]
+    fn monthly_returns_to_the_day_it_started_on() {
+        let monthly = Recurrence::Monthly { day: None };
+        let january = date(2025, 1, 31);
+        assert_eq!(monthly.next_due(Some(&january)), Some(date(2025, 2, 28)));
+
+        let following = monthly.following(Some(&january));
+        assert_eq!(following, Recurrence::Monthly { day: Some(31) });
+        assert_eq!(
+            following.next_due(Some(&date(2025, 2, 28))),
+            Some(date(2025, 3, 31))
+        );
+        assert_eq!(
+            following.next_due(Some(&date(2025, 3, 31))),
+            Some(date(2025, 4, 30))
+        );
+        assert_eq!(
+            following.next_due(Some(&date(2024, 1, 31))),
+            Some(date(2024, 2, 29))
+        );
+    }
+
+    #[test]
+    fn monthly_wraps_around_the_year() {
+        let monthly = Recurrence::Monthly { day: None };
+        assert_eq!(
+            monthly.next_due(Some(&timed(2025, 12, 15, 8, 45))),
+            Some(timed(2026, 1, 15, 8, 45))
+        );
+    }
+
+    #[test]
+    fn after_completion_counts_from_today() {
+        let after = Recurrence::AfterCompletion { days: 3 };
+        assert_eq!(after.next_due(Some(&date(2020, 1, 1))), DueDate::in_days(3));
+
+        let after = Recurrence::AfterCompletion { days: 2 };
+        let next = after.next_due(Some(&timed(2020, 1, 1, 9, 15))).unwrap();
+        assert!(next.has_time());
+        assert_eq!(next.ymd(), DueDate::in_days(2).unwrap().ymd());
+        assert_eq!(
+            (next.date_time().hour(), next.date_time().minute()),
+            (9, 15)
+        );
+    }
+
+    #[test]
+    fn monthly_without_a_day_reads_old_files() {
+        let recurrence: Recurrence = serde_json::from_str(r#"{"kind":"monthly"}"#).unwrap();
+        assert_eq!(recurrence, Recurrence::Monthly { day: None });
+        assert_eq!(
+            serde_json::to_string(&recurrence).unwrap(),
+            r#"{"kind":"monthly"}"#
+        );
+    }
+}
@@ -0,0 +1,426 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Mutex, OnceLock,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use gtk::{gio::ListStore, glib::DateTime, prelude::*};
+use gtk_rs_test::list_store_ser::ListStoreSer;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    collection_object::CollectionObject,
+    data_path,
+    task_object::{self, TaskObject},
+};
+
+use json_file::JsonFile;
+#[cfg(feature = "sqlite")]
+use sqlite::Sqlite;
+
+mod json_file;
+mod merge;
+mod migration;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+/// Errors of the storage, which may be passed on from the thread writing saves
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// Place the collections are persisted in
+///
+/// Backends exchange the document produced by `serialize`, which unlike the objects
+/// it describes can be handed to the thread writing saves.
+pub trait Backend: Send + Sync {
+    /// Read the stored document, `None` if nothing was stored yet
+    fn read(&self) -> Result<Option<Value>, Error>;
+    /// Store `data`, replacing what was stored before
+    fn write(&self, data: &Value) -> Result<(), Error>;
+    /// Move the stored data aside after it turned out to be unreadable
+    ///
+    /// Returns the new location of the data.
+    fn quarantine(&self) -> io::Result<PathBuf>;
+    /// File holding the stored data, watched for changes made by others
+    fn path(&self) -> &Path;
+}
+
+const FILE_NAME: &str = "collections.json";
+#[cfg(feature = "sqlite")]
+const DATABASE_NAME: &str = "collections.sqlite3";
+const BACKUP_DIR: &str = "backups";
+/// Number of backups kept, older ones are deleted
+const MAX_BACKUPS: usize = 10;
+/// Minimum number of seconds between two backups taken while saving
+const BACKUP_INTERVAL: i64 = 10 * 60;
+
+/// Document last read from or written to the backend
+///
+/// It is the common ancestor when merging changes made by others. Held while writing,
+/// as saves may happen on a background thread.
+static STORED: Mutex<Option<Value>> = Mutex::new(None);
+
+/// Whether saving is refused, as the stored data could neither be read nor moved aside
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Earlier state of the data file, stored as
+/// `backups/collections-<unix time>.<microseconds>.json`
+///
+/// Backups written before microseconds were part of the name are read as well.
+pub struct Backup {
+    path: PathBuf,
+    created: DateTime,
+}
+
+impl Backup {
+    fn from_path(path: PathBuf) -> Option<Self> {
+        let time = path
+            .file_name()?
+            .to_str()?
+            .strip_prefix("collections-")?
+            .strip_suffix(".json")?;
+        let (seconds, microseconds) = time.split_once('.').unwrap_or((time, "0"));
+        let seconds = seconds.parse::<i64>().ok()?;
+        let microseconds = microseconds.parse::<u32>().ok()?;
+        let created = DateTime::from_unix_local(seconds)
+            .ok()?
+            .add_seconds(f64::from(microseconds) / 1e6)
+            .ok()?;
+
+        Some(Self { path, created })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn created(&self) -> &DateTime {
+        &self.created
+    }
+
+    /// Time of the backup, e.g. "14 Mar 2025, 17:30:12"
+    pub fn label(&self) -> String {
+        self.created
+            .format("%e %b %Y, %H:%M:%S")
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// Storage backend in use, SQLite when built with the `sqlite` feature
+fn backend() -> &'static dyn Backend {
+    static BACKEND: OnceLock<Box<dyn Backend>> = OnceLock::new();
+
+    BACKEND
+        .get_or_init(|| {
+            let json_file = JsonFile::new(data_path().join(FILE_NAME));
+            #[cfg(feature = "sqlite")]
+            return Box::new(Sqlite::new(data_path().join(DATABASE_NAME), json_file));
+            #[cfg(not(feature = "sqlite"))]
+            Box::new(json_file)
+        })
+        .as_ref()
+}
+
+/// Read the stored collections
+pub fn load() -> Result<Vec<CollectionObject>, Error> {
+    let mut stored = STORED.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(data) = backend().read()? else {
+        return Ok(Vec::new());
+    };
+    back_up_newer(&data);
+
+    let data = upgrade(data)?;
+    let collections = from_document(data.clone())?;
+    *stored = Some(data);
+
+    Ok(collections)
+}
+
+/// Read the collections from a file in the format of the data file, such as a backup
+pub fn load_from(path: &Path) -> Result<Vec<CollectionObject>, Error> {
+    from_document(upgrade(serde_json::from_slice(&fs::read(path)?)?)?)
+}
+
+/// Stored collections changed by someone else, see `read_changes`
+pub struct Changes {
+    /// Document last read or written before, the common ancestor
+    ancestor: Value,
+    theirs: Value,
+}
+
+/// Read the stored collections if someone else changed them since they were last read
+/// or written
+///
+/// Runs on any thread, the changes are taken over by `apply_changes`.
+pub fn read_changes() -> Result<Option<Changes>, Error> {
+    let mut stored = STORED.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(theirs) = backend().read()? else {
+        return Ok(None);
+    };
+    back_up_newer(&theirs);
+    let theirs = upgrade(theirs)?;
+    // Also the case when the change was our own save
+    if stored.as_ref() == Some(&theirs) {
+        return Ok(None);
+    }
+
+    let ancestor = stored.replace(theirs.clone()).unwrap_or(Value::Null);
+    Ok(Some(Changes { ancestor, theirs }))
+}
+
+/// Merge `changes` into `collections`
+///
+/// Changes on both sides are kept, see `merge`. Objects are updated in place, so that
+/// views showing them stay as they are. Returns whether `collections` changed.
+pub fn apply_changes(collections: &ListStore, changes: Changes) -> Result<bool, Error> {
+    let ours = serialize(collections)?;
+    let merged = merge::merge(&changes.ancestor, &ours, &changes.theirs);
+    if merged == ours {
+        return Ok(false);
+    }
+
+    update(collections, from_document(merged)?);
+    Ok(true)
+}
+
+/// Make `collections` hold `newer`, keeping the objects whose ids are known
+fn update(collections: &ListStore, newer: Vec<CollectionObject>) {
+    let current: Vec<CollectionObject> = collections
+        .iter::<CollectionObject>()
+        .map(Result::unwrap)
+        .collect();
+    let mut existing: HashMap<task_object::IdType, TaskObject> = HashMap::new();
+    for collection_object in &current {
+        task_object::collect_tasks(&collection_object.tasks(), &mut existing);
+    }
+
+    let updated: Vec<CollectionObject> = newer
+        .iter()
+        .map(|newer| {
+            let collection_object = current
+                .iter()
+                .find(|c| c.get_id() == newer.get_id())
+                .unwrap_or(newer)
+                .clone();
+            collection_object.update_from(newer, &existing);
+            collection_object
+        })
+        .collect();
+
+    if current != updated {
+        collections.splice(0, collections.n_items(), &updated);
+    }
+}
+
+/// Bring a document of any version to the current one, see `migration`
+///
+/// Documents of newer versions are read as they are, skipping the fields unknown to this
+/// version. Callers back them up first, see `back_up_newer`.
+fn upgrade(data: Value) -> Result<Value, Error> {
+    if migration::version(&data)? > migration::CURRENT_VERSION {
+        return Ok(data);
+    }
+
+    migration::migrate(data)
+}
+
+/// Back up the stored document `data` if it was written by a newer version, as saving
+/// discards what this version doesn't understand
+///
+/// Failing to do so doesn't keep the collections from being read.
+fn back_up_newer(data: &Value) {
+    let Ok(version) = migration::version(data) else {
+        return;
+    };
+    if version <= migration::CURRENT_VERSION {
+        return;
+    }
+
+    eprintln!(
+        "Collections were written by a newer version ({version}), unknown fields are dropped on save"
+    );
+    let result = serde_json::to_vec(data)
+        .map_err(Error::from)
+        .and_then(|contents| Ok(write_backup(&contents)?));
+    if let Err(err) = result {
+        eprintln!("Error occurred trying to back up collections: {err}");
+    }
+}
+
+/// Turn a document of the current version into collections
+fn from_document(mut data: Value) -> Result<Vec<CollectionObject>, Error> {
+    let collections = data
+        .get_mut("collections")
+        .map(Value::take)
+        .ok_or("data file has no collections")?;
+    let v: ListStoreSer<CollectionObject> = serde_json::from_value(collections)?;
+
+    Ok(v.extract()
+        .iter::<CollectionObject>()
+        .map(Result::unwrap)
+        .collect())
+}
+
+/// Move the unreadable stored data aside, so that saving doesn't overwrite it
+///
+/// Returns the new location of the data.
+pub fn quarantine() -> io::Result<PathBuf> {
+    backend().quarantine()
+}
+
+/// Refuse saving, see `READ_ONLY`
+pub fn refuse_saving() {
+    READ_ONLY.store(true, Ordering::SeqCst);
+}
+
+/// Allow saving again after `refuse_saving`
+///
+/// The stored data is moved aside first. Saving stays refused if that fails, so that it
+/// isn't overwritten.
+pub fn allow_saving() -> io::Result<()> {
+    if READ_ONLY.load(Ordering::SeqCst) {
+        quarantine()?;
+    }
+    READ_ONLY.store(false, Ordering::SeqCst);
+
+    Ok(())
+}
+
+/// File holding the stored collections
+pub fn path() -> PathBuf {
+    backend().path().to_path_buf()
+}
+
+/// Store `collections`, replacing what was stored before
+pub fn save(collections: &ListStore) -> Result<(), Error> {
+    write(&serialize(collections)?)
+}
+
+/// Store `data`, as produced by `serialize`
+///
+/// The previously stored data is backed up first, unless a backup was taken recently.
+/// Safe to call from any thread, concurrent writes are serialized.
+pub fn write(data: &Value) -> Result<(), Error> {
+    if READ_ONLY.load(Ordering::SeqCst) {
+        return Err("saving is disabled until a backup is restored".into());
+    }
+    let mut stored = STORED.lock().unwrap_or_else(|e| e.into_inner());
+
+    if backup_due()
+        && let Ok(Some(previous)) = backend().read()
+        && previous != *data
+    {
+        write_backup(&serde_json::to_vec(&previous)?)?;
+    }
+
+    backend().write(data)?;
+    *stored = Some(data.clone());
+
+    Ok(())
+}
+
+/// Store `collections` as a new backup
+pub fn save_backup(collections: &ListStore) -> Result<(), Error> {
+    let contents = serde_json::to_vec(&serialize(collections)?)?;
+    let _guard = STORED.lock().unwrap_or_else(|e| e.into_inner());
+    write_backup(&contents)?;
+
+    Ok(())
+}
+
+/// All backups, newest first
+pub fn backups() -> Vec<Backup> {
+    let Ok(entries) = fs::read_dir(data_path().join(BACKUP_DIR)) else {
+        return Vec::new();
+    };
+
+    let mut backups: Vec<Backup> = entries
+        .filter_map(|entry| Backup::from_path(entry.ok()?.path()))
+        .collect();
+    backups.sort_by(|a, b| b.created.cmp(&a.created));
+
+    backups
+}
+
+/// Document describing `collections`, in the format of the data file
+pub fn serialize(collections: &ListStore) -> serde_json::Result<Value> {
+    #[derive(Serialize)]
+    struct Envelope {
+        version: u64,
+        collections: ListStoreSer<CollectionObject>,
+    }
+
+    serde_json::to_value(Envelope {
+        version: migration::CURRENT_VERSION,
+        collections: ListStoreSer::new(collections.clone()),
+    })
+}
+
+fn backup_due() -> bool {
+    let Ok(now) = DateTime::now_local() else {
+        return true;
+    };
+
+    backups()
+        .first()
+        .is_none_or(|newest| now.difference(newest.created()).as_seconds() >= BACKUP_INTERVAL)
+}
+
+fn write_backup(contents: &[u8]) -> io::Result<()> {
+    let dir = data_path().join(BACKUP_DIR);
+    fs::create_dir_all(&dir)?;
+    let now = DateTime::now_utc().map_err(io::Error::other)?;
+
+    // Backups written in the same microsecond must not replace each other
+    let (seconds, mut microseconds) = (now.to_unix(), now.microsecond());
+    let mut path = dir.join(format!("collections-{seconds}.{microseconds:06}.json"));
+    while path.exists() {
+        microseconds += 1;
+        path = dir.join(format!("collections-{seconds}.{microseconds:06}.json"));
+    }
+    write_atomic(&path, contents)?;
+
+    for backup in backups().iter().skip(MAX_BACKUPS) {
+        if let Err(err) = fs::remove_file(backup.path()) {
+            eprintln!(
+                "Error occurred trying to remove backup {}: {err}",
+                backup.path().display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Write `contents` to a temporary file next to `path`, flush it to disk and move it
+/// over `path`, so that `path` holds either the old or the new content at any time
+fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let result = (|| {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+
+        fs::rename(&tmp_path, path)?;
+        // Make the rename itself durable
+        if let Some(dir) = path.parent() {
+            File::open(dir)?.sync_all()?;
+        }
+
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+
+    result
+}
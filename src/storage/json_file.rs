@@ -0,0 +1,68 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use gtk::glib::DateTime;
+use serde_json::Value;
+
+use super::{Backend, Error, write_atomic};
+
+/// Collections stored as a single JSON document, rewritten on every save
+pub struct JsonFile {
+    path: PathBuf,
+}
+
+impl JsonFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl Backend for JsonFile {
+    /// The file is missing on the first start, and empty if it was created by a
+    /// version before saving became atomic
+    fn read(&self) -> Result<Option<Value>, Error> {
+        match fs::read(&self.path) {
+            Ok(contents) if contents.is_empty() => Ok(None),
+            Ok(contents) => Ok(Some(serde_json::from_slice(&contents)?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn write(&self, data: &Value) -> Result<(), Error> {
+        let contents = serde_json::to_vec(data)?;
+        if fs::read(&self.path).is_ok_and(|previous| previous == contents) {
+            return Ok(());
+        }
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        write_atomic(&self.path, &contents)?;
+
+        Ok(())
+    }
+
+    fn quarantine(&self) -> io::Result<PathBuf> {
+        quarantine_file(&self.path, "json")
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Rename `path` to `collections-unreadable-<unix time>.<extension>` in its directory
+pub(super) fn quarantine_file(path: &Path, extension: &str) -> io::Result<PathBuf> {
+    let now = DateTime::now_utc().map_err(io::Error::other)?;
+    let target = path.with_file_name(format!(
+        "collections-unreadable-{}.{extension}",
+        now.to_unix()
+    ));
+
+    fs::rename(path, &target)?;
+
+    Ok(target)
+}
@@ -0,0 +1,227 @@
+use serde_json::{Map, Value};
+
+/// Three-way merge of documents in the format of the data file
+///
+/// `base` is the document both sides started from, `ours` the one in memory and
+/// `theirs` the one found on disk. Collections and tasks are matched by id and merged
+/// field by field, taking the side that changed a field. When both sides changed the
+/// same field, ours wins, so that in-memory edits are never lost.
+pub fn merge(base: &Value, ours: &Value, theirs: &Value) -> Value {
+    merge_value(Some(base), ours, theirs)
+}
+
+fn merge_value(base: Option<&Value>, ours: &Value, theirs: &Value) -> Value {
+    if ours == theirs {
+        return ours.clone();
+    }
+
+    match (ours, theirs) {
+        (Value::Object(o), Value::Object(t)) => {
+            let base = base.and_then(Value::as_object);
+            Value::Object(merge_object(base, o, t))
+        }
+        (Value::Array(o), Value::Array(t)) if has_ids(o) && has_ids(t) => {
+            let base = base.and_then(Value::as_array).filter(|b| has_ids(b));
+            Value::Array(merge_list(base.map(Vec::as_slice).unwrap_or(&[]), o, t))
+        }
+        _ if base == Some(ours) => theirs.clone(),
+        _ => ours.clone(),
+    }
+}
+
+fn merge_object(
+    base: Option<&Map<String, Value>>,
+    ours: &Map<String, Value>,
+    theirs: &Map<String, Value>,
+) -> Map<String, Value> {
+    let mut merged = Map::new();
+
+    for key in ours.keys().chain(theirs.keys()) {
+        if merged.contains_key(key) {
+            continue;
+        }
+        let b = base.and_then(|b| b.get(key));
+        let value = match (ours.get(key), theirs.get(key)) {
+            (Some(o), Some(t)) => Some(merge_value(b, o, t)),
+            // Removed on one side, keep it only if the other side changed it
+            (Some(o), None) => (b != Some(o)).then(|| o.clone()),
+            (None, Some(t)) => (b != Some(t)).then(|| t.clone()),
+            (None, None) => None,
+        };
+        if let Some(value) = value {
+            merged.insert(key.clone(), value);
+        }
+    }
+
+    merged
+}
+
+/// Merge lists of collections or tasks, matching their elements by id
+fn merge_list(base: &[Value], ours: &[Value], theirs: &[Value]) -> Vec<Value> {
+    let find = |list: &[Value], id: &str| list.iter().find(|v| self::id(v) == Some(id)).cloned();
+
+    // Follow the order of the side that reordered the list
+    let (leading, other) = if ids(ours) == ids(base) {
+        (theirs, ours)
+    } else {
+        (ours, theirs)
+    };
+
+    let mut merged: Vec<Value> = Vec::new();
+    let insert_after = |merged: &mut Vec<Value>, previous: Option<&str>, value: Value| {
+        let position = match previous {
+            Some(p) => merged
+                .iter()
+                .position(|v| id(v) == Some(p))
+                .map_or(merged.len(), |p| p + 1),
+            None => 0,
+        };
+        merged.insert(position, value);
+    };
+
+    for (i, value) in leading.iter().chain(other).enumerate() {
+        let id = self::id(value).unwrap_or_default();
+        if merged.iter().any(|v| self::id(v) == Some(id)) {
+            continue;
+        }
+        let from_leading = i < leading.len();
+        let (o, t) = if std::ptr::eq(leading, ours) == from_leading {
+            (Some(value.clone()), find(theirs, id))
+        } else {
+            (find(ours, id), Some(value.clone()))
+        };
+        let b = find(base, id);
+
+        let value = match (o, t) {
+            (Some(o), Some(t)) => merge_value(b.as_ref(), &o, &t),
+            // Removed on one side, keep it only if the other side changed it
+            (Some(v), None) | (None, Some(v)) if b.as_ref() == Some(&v) => continue,
+            (Some(v), None) | (None, Some(v)) => v,
+            (None, None) => continue,
+        };
+
+        if from_leading {
+            merged.push(value);
+        } else {
+            // Elements only known to the other side go after their predecessor there
+            let position = other.iter().position(|v| self::id(v) == Some(id)).unwrap();
+            let previous = position.checked_sub(1).and_then(|p| self::id(&other[p]));
+            insert_after(&mut merged, previous, value);
+        }
+    }
+
+    merged
+}
+
+fn id(value: &Value) -> Option<&str> {
+    value.get("id").and_then(Value::as_str)
+}
+
+fn ids(list: &[Value]) -> Vec<Option<&str>> {
+    list.iter().map(id).collect()
+}
+
+/// Whether `list` is a list of collections or tasks, which an empty list may be
+fn has_ids(list: &[Value]) -> bool {
+    list.iter().all(|v| id(v).is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// Data file with a single collection holding `tasks`
+    fn document(tasks: Value) -> Value {
+        json!({
+            "version": 1,
+            "collections": [{ "id": "c", "title": "Inbox", "tasks": tasks }],
+        })
+    }
+
+    fn task(id: &str, name: &str) -> Value {
+        json!({ "id": id, "name": name, "tags": [] })
+    }
+
+    #[test]
+    fn ours_wins_when_both_changed_a_field() {
+        let merged = merge(
+            &document(json!([task("a", "Call Mom")])),
+            &document(json!([task("a", "Call Dad")])),
+            &document(json!([task("a", "Call Grandma")])),
+        );
+        assert_eq!(merged, document(json!([task("a", "Call Dad")])));
+    }
+
+    #[test]
+    fn changes_of_one_side_are_taken() {
+        let base = document(json!([task("a", "Call Mom"), task("b", "Buy milk")]));
+        let ours = document(json!([task("a", "Call Dad"), task("b", "Buy milk")]));
+        let theirs = document(json!([task("a", "Call Mom"), task("b", "Buy bread")]));
+        assert_eq!(
+            merge(&base, &ours, &theirs),
+            document(json!([task("a", "Call Dad"), task("b", "Buy bread")]))
+        );
+    }
+
+    #[test]
+    fn edited_tasks_survive_a_delete() {
+        let base = document(json!([task("a", "Call Mom"), task("b", "Buy milk")]));
+        let ours = document(json!([task("a", "Call Dad"), task("b", "Buy milk")]));
+        let theirs = document(json!([task("b", "Buy milk")]));
+        assert_eq!(merge(&base, &ours, &theirs), ours);
+        assert_eq!(merge(&base, &theirs, &ours), ours);
+    }
+
+    #[test]
+    fn unchanged_tasks_are_deleted() {
+        let base = document(json!([task("a", "Call Mom"), task("b", "Buy milk")]));
+        let theirs = document(json!([task("b", "Buy milk")]));
+        assert_eq!(merge(&base, &base, &theirs), theirs);
+        assert_eq!(merge(&base, &theirs, &base), theirs);
+    }
+
+    #[test]
+    fn order_follows_the_side_that_reordered() {
+        let base = document(json!([task("a", "A"), task("b", "B"), task("c", "C")]));
+        let reordered = document(json!([task("c", "C"), task("a", "A"), task("b", "B")]));
+        assert_eq!(merge(&base, &base, &reordered), reordered);
+        assert_eq!(merge(&base, &reordered, &base), reordered);
+    }
+
+    #[test]
+    fn added_tasks_go_after_their_predecessor() {
+        let base = document(json!([task("a", "A"), task("b", "B"), task("c", "C")]));
+        let ours = document(json!([task("c", "C"), task("a", "A"), task("b", "B")]));
+        let theirs = document(json!([
+            task("a", "A"),
+            task("b", "B"),
+            task("x", "X"),
+            task("c", "C"),
+        ]));
+        assert_eq!(
+            merge(&base, &ours, &theirs),
+            document(json!([
+                task("c", "C"),
+                task("a", "A"),
+                task("b", "B"),
+                task("x", "X"),
+            ]))
+        );
+    }
+
+    #[test]
+    fn tags_are_merged_as_a_whole() {
+        let with_tags = |tags: Value| document(json!([{ "id": "a", "name": "A", "tags": tags }]));
+        let base = with_tags(json!(["family"]));
+        let ours = with_tags(json!(["family", "phone"]));
+        let theirs = with_tags(json!(["work"]));
+        assert_eq!(merge(&base, &ours, &theirs), ours);
+        assert_eq!(merge(&base, &base, &theirs), theirs);
+        assert_eq!(
+            merge(&base, &with_tags(json!([])), &theirs),
+            with_tags(json!([]))
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use serde_json::{Value, json};
+
+use super::Error;
+
+/// Version of the data file written by this build
+pub const CURRENT_VERSION: u64 = 1;
+
+/// Steps upgrading the data file by one version, the step at index `i` turns
+/// version `i` into version `i + 1`
+const MIGRATIONS: [fn(Value) -> Result<Value, Error>; CURRENT_VERSION as usize] =
+    [wrap_in_envelope];
+
+/// Version of a data file, files written before versioning are a bare array of
+/// collections and count as version 0
+pub fn version(data: &Value) -> Result<u64, Error> {
+    match data {
+        Value::Array(_) => Ok(0),
+        Value::Object(envelope) => envelope
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| "data file is missing its version".into()),
+        _ => Err("data file is neither a list nor an object".into()),
+    }
+}
+
+/// Upgrade a data file of an older version to `CURRENT_VERSION`
+///
+/// Files of newer versions can't be brought to this version and are refused.
+pub fn migrate(mut data: Value) -> Result<Value, Error> {
+    let version = version(&data)?;
+    if version > CURRENT_VERSION {
+        return Err(
+            format!("data file of version {version} is newer than {CURRENT_VERSION}").into(),
+        );
+    }
+
+    for migration in MIGRATIONS.iter().skip(version as usize) {
+        data = migration(data)?;
+    }
+
+    Ok(data)
+}
+
+/// Version 0 to 1: put the list of collections into an object carrying the version
+fn wrap_in_envelope(data: Value) -> Result<Value, Error> {
+    Ok(json!({
+        "version": 1,
+        "collections": data,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bare_lists_are_wrapped() {
+        let collections = json!([{ "id": "inbox", "title": "Inbox", "tasks": [] }]);
+        assert_eq!(version(&collections).unwrap(), 0);
+        assert_eq!(
+            migrate(collections.clone()).unwrap(),
+            json!({ "version": CURRENT_VERSION, "collections": collections })
+        );
+    }
+
+    #[test]
+    fn current_files_are_unchanged() {
+        let data = json!({ "version": CURRENT_VERSION, "collections": [] });
+        assert_eq!(migrate(data.clone()).unwrap(), data);
+    }
+
+    #[test]
+    fn newer_files_are_refused() {
+        let data = json!({ "version": CURRENT_VERSION + 1, "collections": [] });
+        assert_eq!(version(&data).unwrap(), CURRENT_VERSION + 1);
+        assert!(migrate(data).is_err());
+    }
+
+    #[test]
+    fn other_documents_are_refused() {
+        assert!(migrate(json!("collections")).is_err());
+        assert!(migrate(json!(1)).is_err());
+        assert!(migrate(json!({ "collections": [] })).is_err());
+    }
+}
@@ -0,0 +1,466 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+};
+
+use rusqlite::{Connection, OptionalExtension, params};
+use serde_json::{Map, Value};
+
+use super::{Backend, Error, json_file::JsonFile};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS meta (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS collections (
+        id TEXT PRIMARY KEY,
+        position INTEGER NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS tasks (
+        id TEXT PRIMARY KEY,
+        collection_id TEXT NOT NULL,
+        parent_id TEXT,
+        position INTEGER NOT NULL,
+        data TEXT NOT NULL
+    );
+";
+
+/// Collections stored in an SQLite database with a row per collection and task
+///
+/// Each row holds the JSON of its object without the nested lists. Saving compares the
+/// document with the rows written last and only touches the ones that changed, so
+/// editing a task writes a single row however many tasks there are. Only the writes are
+/// incremental though, each save still serializes and compares the whole document.
+///
+/// Until the database has been written once, reading falls back to `json_file`, which
+/// imports the collections saved by the JSON backend.
+pub struct Sqlite {
+    path: PathBuf,
+    json_file: JsonFile,
+    state: Mutex<Option<State>>,
+}
+
+struct State {
+    connection: Connection,
+    /// Content of the database, `None` while it is empty
+    stored: Option<Rows>,
+}
+
+#[derive(Default)]
+struct Rows {
+    version: u64,
+    collections: HashMap<String, CollectionRow>,
+    tasks: HashMap<String, TaskRow>,
+}
+
+#[derive(PartialEq)]
+struct CollectionRow {
+    position: i64,
+    data: String,
+}
+
+#[derive(PartialEq)]
+struct TaskRow {
+    collection_id: String,
+    parent_id: Option<String>,
+    position: i64,
+    data: String,
+}
+
+/// Tasks by the collection and parent task they belong to, in order
+type Children<'a> = HashMap<(&'a str, Option<&'a str>), Vec<(&'a str, &'a TaskRow)>>;
+
+impl Sqlite {
+    pub fn new(path: PathBuf, json_file: JsonFile) -> Self {
+        Self {
+            path,
+            json_file,
+            state: Default::default(),
+        }
+    }
+
+    /// Open the database on first use
+    fn state(&self) -> Result<MutexGuard<'_, Option<State>>, Error> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.is_none() {
+            if let Some(dir) = self.path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let connection = Connection::open(&self.path)?;
+            connection.execute_batch(SCHEMA)?;
+            let stored = read_rows(&connection)?;
+
+            *state = Some(State { connection, stored });
+        }
+
+        Ok(state)
+    }
+}
+
+impl Backend for Sqlite {
+    fn read(&self) -> Result<Option<Value>, Error> {
+        let mut state = self.state()?;
+        let state = state.as_mut().unwrap();
+        // Another instance may have written since the rows were cached
+        state.stored = read_rows(&state.connection)?;
+
+        match &state.stored {
+            Some(rows) => Ok(Some(rows.to_document()?)),
+            None => self.json_file.read(),
+        }
+    }
+
+    fn write(&self, data: &Value) -> Result<(), Error> {
+        let mut state = self.state()?;
+        let state = state.as_mut().unwrap();
+        let rows = Rows::from_document(data)?;
+        let empty = Rows::default();
+        let stored = state.stored.as_ref().unwrap_or(&empty);
+
+        write_rows(&mut state.connection, stored, &rows)?;
+        state.stored = Some(rows);
+
+        Ok(())
+    }
+
+    fn quarantine(&self) -> io::Result<PathBuf> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        // Nothing was written yet, so the unreadable data came from the JSON file
+        if state.as_ref().is_some_and(|s| s.stored.is_none()) || !self.path.exists() {
+            return self.json_file.quarantine();
+        }
+
+        // Close the database before moving it
+        *state = None;
+        super::json_file::quarantine_file(&self.path, "sqlite3")
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Make the database hold `rows`, touching only the rows that differ from `stored`
+fn write_rows(connection: &mut Connection, stored: &Rows, rows: &Rows) -> Result<(), Error> {
+    let transaction = connection.transaction()?;
+    if rows.version != stored.version {
+        transaction.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('version', ?1)",
+            params![rows.version.to_string()],
+        )?;
+    }
+    for id in stored.collections.keys() {
+        if !rows.collections.contains_key(id) {
+            transaction.execute("DELETE FROM collections WHERE id = ?1", params![id])?;
+        }
+    }
+    for (id, row) in &rows.collections {
+        if stored.collections.get(id) != Some(row) {
+            transaction.execute(
+                "INSERT OR REPLACE INTO collections (id, position, data) VALUES (?1, ?2, ?3)",
+                params![id, row.position, row.data],
+            )?;
+        }
+    }
+    for id in stored.tasks.keys() {
+        if !rows.tasks.contains_key(id) {
+            transaction.execute("DELETE FROM tasks WHERE id = ?1", params![id])?;
+        }
+    }
+    for (id, row) in &rows.tasks {
+        if stored.tasks.get(id) != Some(row) {
+            transaction.execute(
+                "INSERT OR REPLACE INTO tasks (id, collection_id, parent_id, position, data)
+                    VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![id, row.collection_id, row.parent_id, row.position, row.data],
+            )?;
+        }
+    }
+    transaction.commit()?;
+
+    Ok(())
+}
+
+/// Load the content of the database, `None` if nothing was written yet
+fn read_rows(connection: &Connection) -> Result<Option<Rows>, Error> {
+    let Some(version) = connection
+        .query_row("SELECT value FROM meta WHERE key = 'version'", [], |row| {
+            row.get::<_, String>(0)
+        })
+        .optional()?
+    else {
+        return Ok(None);
+    };
+
+    let mut rows = Rows {
+        version: version.parse()?,
+        ..Default::default()
+    };
+    let mut statement = connection.prepare("SELECT id, position, data FROM collections")?;
+    for row in statement.query_map([], |row| {
+        Ok((
+            row.get(0)?,
+            CollectionRow {
+                position: row.get(1)?,
+                data: row.get(2)?,
+            },
+        ))
+    })? {
+        let (id, row) = row?;
+        rows.collections.insert(id, row);
+    }
+    let mut statement =
+        connection.prepare("SELECT id, collection_id, parent_id, position, data FROM tasks")?;
+    for row in statement.query_map([], |row| {
+        Ok((
+            row.get(0)?,
+            TaskRow {
+                collection_id: row.get(1)?,
+                parent_id: row.get(2)?,
+                position: row.get(3)?,
+                data: row.get(4)?,
+            },
+        ))
+    })? {
+        let (id, row) = row?;
+        rows.tasks.insert(id, row);
+    }
+
+    Ok(Some(rows))
+}
+
+impl Rows {
+    /// Split a document into a row per collection and task
+    fn from_document(data: &Value) -> Result<Self, Error> {
+        let mut rows = Rows {
+            version: data
+                .get("version")
+                .and_then(Value::as_u64)
+                .ok_or("document has no version")?,
+            ..Default::default()
+        };
+
+        let collections = data
+            .get("collections")
+            .and_then(Value::as_array)
+            .ok_or("document has no collections")?;
+        for (position, collection) in collections.iter().enumerate() {
+            let (id, tasks, data) = split_object(collection, "tasks")?;
+            rows.add_tasks(&id, None, &tasks)?;
+            let row = CollectionRow {
+                position: position as i64,
+                data,
+            };
+            // A row per id, so one of two objects sharing it would be lost
+            if rows.collections.insert(id.clone(), row).is_some() {
+                return Err(format!("duplicate collection id {id}").into());
+            }
+        }
+
+        Ok(rows)
+    }
+
+    fn add_tasks(
+        &mut self,
+        collection_id: &str,
+        parent_id: Option<&str>,
+        tasks: &[Value],
+    ) -> Result<(), Error> {
+        for (position, task) in tasks.iter().enumerate() {
+            let (id, subtasks, data) = split_object(task, "subtasks")?;
+            self.add_tasks(collection_id, Some(&id), &subtasks)?;
+            let row = TaskRow {
+                collection_id: collection_id.to_string(),
+                parent_id: parent_id.map(str::to_string),
+                position: position as i64,
+                data,
+            };
+            if self.tasks.insert(id.clone(), row).is_some() {
+                return Err(format!("duplicate task id {id}").into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reassemble the document the rows were split from
+    fn to_document(&self) -> Result<Value, Error> {
+        let mut children = Children::new();
+        for (id, row) in &self.tasks {
+            children
+                .entry((&row.collection_id, row.parent_id.as_deref()))
+                .or_default()
+                .push((id, row));
+        }
+        children
+            .values_mut()
+            .for_each(|tasks| tasks.sort_by_key(|(_, row)| row.position));
+
+        fn tasks(
+            children: &Children,
+            collection_id: &str,
+            parent_id: Option<&str>,
+        ) -> Result<Value, Error> {
+            let Some(rows) = children.get(&(collection_id, parent_id)) else {
+                return Ok(Value::Array(Vec::new()));
+            };
+
+            rows.iter()
+                .map(|(id, row)| {
+                    let mut task: Map<String, Value> = serde_json::from_str(&row.data)?;
+                    task.insert(
+                        String::from("subtasks"),
+                        tasks(children, collection_id, Some(id))?,
+                    );
+                    Ok(Value::Object(task))
+                })
+                .collect()
+        }
+
+        let mut collections: Vec<(&String, &CollectionRow)> = self.collections.iter().collect();
+        collections.sort_by_key(|(_, row)| row.position);
+        let collections = collections
+            .into_iter()
+            .map(|(id, row)| {
+                let mut collection: Map<String, Value> = serde_json::from_str(&row.data)?;
+                collection.insert(String::from("tasks"), tasks(&children, id, None)?);
+                Ok(Value::Object(collection))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(serde_json::json!({
+            "version": self.version,
+            "collections": collections,
+        }))
+    }
+}
+
+/// Take the id and the nested list `children` out of a collection or task, returning
+/// them together with the JSON of the remaining fields
+fn split_object(object: &Value, children: &str) -> Result<(String, Vec<Value>, String), Error> {
+    let mut object = object.as_object().ok_or("expected an object")?.clone();
+    let id = object
+        .get("id")
+        .and_then(Value::as_str)
+        .ok_or("object has no id")?
+        .to_string();
+    let children = match object.remove(children) {
+        Some(Value::Array(children)) => children,
+        _ => Vec::new(),
+    };
+
+    Ok((id, children, Value::Object(object).to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn task(id: &str, name: &str, subtasks: Value) -> Value {
+        json!({ "id": id, "name": name, "subtasks": subtasks })
+    }
+
+    fn document(inbox: Value, work: Value) -> Value {
+        json!({
+            "version": 1,
+            "collections": [
+                { "id": "inbox", "title": "Inbox", "tasks": inbox },
+                { "id": "work", "title": "Work", "tasks": work },
+            ],
+        })
+    }
+
+    /// Write `data` over `stored`, returning the new rows and the number of rows changed
+    fn write(connection: &mut Connection, stored: &Rows, data: &Value) -> (Rows, u64) {
+        let rows = Rows::from_document(data).unwrap();
+        let changes = connection.total_changes();
+        write_rows(connection, stored, &rows).unwrap();
+
+        (rows, connection.total_changes() - changes)
+    }
+
+    #[test]
+    fn only_changed_rows_are_written() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch(SCHEMA).unwrap();
+        assert!(read_rows(&connection).unwrap().is_none());
+
+        let first = document(
+            json!([
+                task("a", "Call Mom", json!([])),
+                task(
+                    "b",
+                    "Plan trip",
+                    json!([task("s", "Book flights", json!([]))])
+                ),
+                task("d", "Buy milk", json!([])),
+            ]),
+            json!([]),
+        );
+        let (stored, changes) = write(&mut connection, &Rows::default(), &first);
+        // The version, two collections and four tasks
+        assert_eq!(changes, 7);
+        assert_eq!(
+            read_rows(&connection)
+                .unwrap()
+                .unwrap()
+                .to_document()
+                .unwrap(),
+            first
+        );
+
+        // Reorder and rename, delete a task and add one to another collection
+        let second = document(
+            json!([
+                task(
+                    "b",
+                    "Plan trip",
+                    json!([task("s", "Book flights", json!([]))])
+                ),
+                task("a", "Call Dad", json!([])),
+            ]),
+            json!([task("e", "Send report", json!([]))]),
+        );
+        let (_, changes) = write(&mut connection, &stored, &second);
+        assert_eq!(changes, 4);
+        assert_eq!(
+            read_rows(&connection)
+                .unwrap()
+                .unwrap()
+                .to_document()
+                .unwrap(),
+            second
+        );
+    }
+
+    #[test]
+    fn duplicate_ids_are_refused() {
+        let tasks = json!([
+            task("a", "Call Mom", json!([])),
+            task("a", "Call Dad", json!([]))
+        ]);
+        assert!(Rows::from_document(&document(tasks, json!([]))).is_err());
+
+        let subtasks = json!([task("a", "Call Mom", json!([]))]);
+        let tasks = json!([
+            task("b", "Plan trip", json!([])),
+            task("a", "Call", subtasks)
+        ]);
+        assert!(Rows::from_document(&document(tasks, json!([]))).is_err());
+
+        let collections = json!({
+            "version": 1,
+            "collections": [
+                { "id": "inbox", "title": "Inbox", "tasks": [] },
+                { "id": "inbox", "title": "Work", "tasks": [] },
+            ],
+        });
+        assert!(Rows::from_document(&collections).is_err());
+    }
+}
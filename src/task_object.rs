@@ -1,27 +1,202 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::HashMap};
 
 use adw::subclass::prelude::*;
-use gtk::glib::{self, Object, VariantTy, prelude::*};
+use gtk::{
+    gio::{
+        ListStore,
+        prelude::{ListModelExt, ListModelExtManual},
+    },
+    glib::{self, DateTime, Object, TimeZone, VariantDict, VariantTy, prelude::*},
+};
+use gtk_rs_test::list_store_ser::ListStoreSer;
 use serde::{
     Deserialize, Serialize,
     de::{self, Visitor},
 };
 
+use crate::{due_date::DueDate, priority::Priority, recurrence::Recurrence};
+
 glib::wrapper! {
     pub struct TaskObject(ObjectSubclass<imp::TaskObjectImp>);
 }
 
-pub type IdType = u64;
+/// Globally unique identifier of a task, stored as a UUID string
+pub type IdType = String;
 
 impl TaskObject {
     pub fn new(name: &str) -> Self {
         Object::builder().property("name", name).build()
     }
 
+    /// Create a task keeping the id it has elsewhere, e.g. in another task manager
+    pub fn with_id(name: &str, id: IdType) -> Self {
+        let task_object = Self::new(name);
+        *task_object.imp().id.borrow_mut() = id;
+
+        task_object
+    }
+
     pub fn get_id(&self) -> IdType {
-        self.imp().id
+        self.imp().id.borrow().clone()
+    }
+
+    /// Give the task a new id, e.g. when its id is already taken by another task
+    pub fn renew_id(&self) {
+        *self.imp().id.borrow_mut() = glib::uuid_string_random().into();
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags().iter().any(|t| t == tag)
+    }
+
+    /// Create the unresolved task following this recurring task, with its due date
+    /// rolled forward according to the recurrence rule
+    pub fn next_occurrence(&self) -> Option<TaskObject> {
+        let recurrence = self.recurrence()?;
+        let next = self.duplicate();
+
+        next.set_due(recurrence.next_due(self.due().as_ref()));
+        next.set_recurrence(Some(recurrence.following(self.due().as_ref())));
+
+        Some(next)
+    }
+
+    /// Deep copy of the task and its subtasks with fresh ids, all of them unresolved
+    fn duplicate(&self) -> TaskObject {
+        let copy = TaskObject::new(&self.name());
+        copy.set_notes(self.notes());
+        copy.set_due(self.due());
+        copy.set_priority(self.priority());
+        copy.set_tags(self.tags());
+        copy.set_recurrence(self.recurrence());
+        for subtask in self.subtasks().iter::<TaskObject>().map(Result::unwrap) {
+            copy.subtasks().append(&subtask.duplicate());
+        }
+        copy.set_derive_checked(self.derive_checked());
+
+        copy
+    }
+
+    /// Take over the stored fields and subtasks of `other`, a newer state of the task
+    ///
+    /// Only fields that differ are set, so that unchanged ones don't notify.
+    fn update_from(&self, other: &TaskObject, existing: &HashMap<IdType, TaskObject>) {
+        if self.name() != other.name() {
+            self.set_name(other.name());
+        }
+        if self.checked() != other.checked() {
+            self.set_checked(other.checked());
+        }
+        if self.notes() != other.notes() {
+            self.set_notes(other.notes());
+        }
+        if self.due() != other.due() {
+            self.set_due(other.due());
+        }
+        if self.priority() != other.priority() {
+            self.set_priority(other.priority());
+        }
+        if self.tags() != other.tags() {
+            self.set_tags(other.tags());
+        }
+        if self.recurrence() != other.recurrence() {
+            self.set_recurrence(other.recurrence());
+        }
+        update_tasks(&self.subtasks(), &other.subtasks(), existing);
+        if self.derive_checked() != other.derive_checked() {
+            self.set_derive_checked(other.derive_checked());
+        }
+        // Take over the timestamps last, as setting the fields above touches them
+        if self.created_at() != other.created_at() {
+            self.set_created_at(other.created_at());
+        }
+        if self.modified_at() != other.modified_at() {
+            self.set_modified_at(other.modified_at());
+        }
+        if self.completed_at() != other.completed_at() {
+            self.set_completed_at(other.completed_at());
+        }
     }
 }
+
+/// Turn user input such as `#release, docs` into a list of distinct tags
+pub fn parse_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in text
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .map(|t| t.trim_start_matches('#'))
+        .filter(|t| !t.is_empty())
+    {
+        if !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+
+    tags
+}
+
+/// Format a timestamp as ISO 8601 in UTC, as stored in the data file
+pub fn format_timestamp(date_time: &DateTime) -> String {
+    date_time
+        .to_utc()
+        .and_then(|d| d.format_iso8601())
+        .map(String::from)
+        .unwrap_or_default()
+}
+
+/// Parse a timestamp written by `format_timestamp`
+pub fn parse_timestamp(text: &str) -> Option<DateTime> {
+    DateTime::from_iso8601(text, Some(&TimeZone::utc())).ok()
+}
+
+/// Find the task with the given id among `tasks` and their subtasks
+///
+/// Returns the ListStore directly containing the task and its position in it.
+pub fn find_task(tasks: &ListStore, id: &str) -> Option<(ListStore, u32, TaskObject)> {
+    for (position, task_object) in tasks.iter::<TaskObject>().map(Result::unwrap).enumerate() {
+        if *task_object.imp().id.borrow() == id {
+            return Some((tasks.clone(), position as u32, task_object));
+        }
+        if let Some(found) = find_task(&task_object.subtasks(), id) {
+            return Some(found);
+        }
+    }
+
+    None
+}
+
+/// Add `tasks` and all their subtasks to `found`, by id
+pub fn collect_tasks(tasks: &ListStore, found: &mut HashMap<IdType, TaskObject>) {
+    for task_object in tasks.iter::<TaskObject>().map(Result::unwrap) {
+        collect_tasks(&task_object.subtasks(), found);
+        found.insert(task_object.get_id(), task_object);
+    }
+}
+
+/// Make `tasks` hold the tasks of `other`, a newer state of the list
+///
+/// Tasks of `existing` which have the id of a task in `other` are updated and kept
+/// rather than replaced, so that views and commands referring to them stay valid.
+pub fn update_tasks(tasks: &ListStore, other: &ListStore, existing: &HashMap<IdType, TaskObject>) {
+    let updated: Vec<TaskObject> = other
+        .iter::<TaskObject>()
+        .map(Result::unwrap)
+        .map(|newer| {
+            let task_object = existing.get(&newer.get_id()).unwrap_or(&newer).clone();
+            task_object.update_from(&newer, existing);
+            task_object
+        })
+        .collect();
+
+    if tasks
+        .iter::<TaskObject>()
+        .map(Result::unwrap)
+        .ne(updated.iter().cloned())
+    {
+        tasks.splice(0, tasks.n_items(), &updated);
+    }
+}
+
 impl Serialize for TaskObject {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -31,7 +206,21 @@ impl Serialize for TaskObject {
     }
 }
 
-const FIELDS: [&'static str; 2] = ["checked", "name"];
+const FIELDS: [&'static str; 13] = [
+    "id",
+    "checked",
+    "name",
+    "notes",
+    "due",
+    "priority",
+    "tags",
+    "recurrence",
+    "subtasks",
+    "derive_checked",
+    "created_at",
+    "modified_at",
+    "completed_at",
+];
 impl<'de> Deserialize<'de> for TaskObject {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -50,24 +239,92 @@ impl<'de> Deserialize<'de> for TaskObject {
             where
                 A: serde::de::MapAccess<'de>,
             {
+                let mut id = None;
                 let mut checked = None;
                 let mut name = None;
-                for _ in 0..2 {
-                    match map.next_key::<String>()?.unwrap().as_str() {
+                let mut notes = None;
+                let mut due = None;
+                let mut priority = None;
+                let mut tags = None;
+                let mut recurrence = None;
+                let mut subtasks = None;
+                let mut derive_checked = None;
+                let mut created_at = None;
+                let mut modified_at = None;
+                let mut completed_at = None;
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "id" => {
+                            id = Some(map.next_value::<IdType>()?);
+                        }
                         "checked" => {
                             checked = Some(map.next_value::<bool>()?);
                         }
                         "name" => {
                             name = Some(map.next_value::<String>()?);
                         }
-                        f => return Err(de::Error::unknown_field(f, &FIELDS)),
+                        "notes" => {
+                            notes = Some(map.next_value::<String>()?);
+                        }
+                        "due" => {
+                            due = map.next_value::<Option<DueDate>>()?;
+                        }
+                        "priority" => {
+                            priority = Some(map.next_value::<Priority>()?);
+                        }
+                        "tags" => {
+                            tags = Some(map.next_value::<Vec<String>>()?);
+                        }
+                        "recurrence" => {
+                            recurrence = map.next_value::<Option<Recurrence>>()?;
+                        }
+                        "subtasks" => {
+                            subtasks = Some(map.next_value::<ListStoreSer<TaskObject>>()?);
+                        }
+                        "derive_checked" => {
+                            derive_checked = Some(map.next_value::<bool>()?);
+                        }
+                        "created_at" => {
+                            created_at = map.next_value::<Option<Timestamp>>()?.map(|t| t.0);
+                        }
+                        "modified_at" => {
+                            modified_at = map.next_value::<Option<Timestamp>>()?.map(|t| t.0);
+                        }
+                        "completed_at" => {
+                            completed_at = map.next_value::<Option<Timestamp>>()?.map(|t| t.0);
+                        }
+                        // Fields written by newer versions
+                        _ => {
+                            map.next_value::<de::IgnoredAny>()?;
+                        }
                     }
                 }
                 let checked = checked.ok_or_else(|| de::Error::missing_field("checked"))?;
-                let name = name.ok_or_else(|| de::Error::missing_field("checked"))?;
+                let name = name.ok_or_else(|| de::Error::missing_field("name"))?;
 
                 let task_object = TaskObject::new(&name);
                 task_object.set_checked(checked);
+                task_object.set_notes(notes.unwrap_or_default());
+                task_object.set_due(due);
+                task_object.set_priority(priority.unwrap_or_default());
+                task_object.set_tags(tags.unwrap_or_default());
+                task_object.set_recurrence(recurrence);
+                if let Some(subtasks) = subtasks {
+                    let subtasks_dest = task_object.subtasks();
+                    for t in subtasks.extract().iter::<TaskObject>().map(Result::unwrap) {
+                        subtasks_dest.append(&t);
+                    }
+                }
+                task_object.set_derive_checked(derive_checked.unwrap_or_default());
+                // Restore the timestamps last, as setting the fields above touches them.
+                // Files written before they were tracked leave them unknown.
+                task_object.set_modified_at(modified_at.clone().or(created_at.clone()));
+                task_object.set_created_at(created_at);
+                task_object.set_completed_at(completed_at);
+                // Files written before ids were persisted keep the freshly generated one
+                if let Some(id) = id {
+                    *task_object.imp().id.borrow_mut() = id;
+                }
 
                 Ok(task_object)
             }
@@ -75,40 +332,134 @@ impl<'de> Deserialize<'de> for TaskObject {
         deserializer.deserialize_struct("TaskObject", &FIELDS, TaskObjectVisitor {})
     }
 }
+
+/// Timestamp in the format of `format_timestamp`
+struct Timestamp(DateTime);
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let text = String::deserialize(deserializer)?;
+
+        parse_timestamp(&text)
+            .map(Timestamp)
+            .ok_or_else(|| de::Error::custom(format!("invalid timestamp \"{text}\"")))
+    }
+}
+/// Tasks are represented as an `a{sv}` dictionary so that optional fields can be left out
 impl ToVariant for TaskObject {
     fn to_variant(&self) -> glib::Variant {
-        (self.checked(), self.name()).to_variant()
+        let dict = VariantDict::new(None);
+        dict.insert("id", self.get_id());
+        dict.insert("checked", self.checked());
+        dict.insert("name", self.name());
+        dict.insert("notes", self.notes());
+        if let Some(due) = self.due() {
+            dict.insert("due", due.to_string());
+        }
+        dict.insert("priority", Into::<&str>::into(self.priority()));
+        dict.insert("tags", self.tags());
+        if let Some(recurrence) = self.recurrence() {
+            dict.insert("recurrence", serde_json::to_string(&recurrence).unwrap());
+        }
+        dict.insert_value(
+            "subtasks",
+            &self
+                .subtasks()
+                .iter::<TaskObject>()
+                .map(Result::unwrap)
+                .collect::<Vec<_>>()
+                .to_variant(),
+        );
+        dict.insert("derive-checked", self.derive_checked());
+        if let Some(created_at) = self.created_at() {
+            dict.insert("created-at", format_timestamp(&created_at));
+        }
+        if let Some(modified_at) = self.modified_at() {
+            dict.insert("modified-at", format_timestamp(&modified_at));
+        }
+        if let Some(completed_at) = self.completed_at() {
+            dict.insert("completed-at", format_timestamp(&completed_at));
+        }
+
+        dict.end()
     }
 }
 impl FromVariant for TaskObject {
     fn from_variant(variant: &glib::Variant) -> Option<Self> {
-        variant.get::<(bool, String)>().map(|(checked, name)| {
-            let t = TaskObject::new(&name);
-            t.set_checked(checked);
-            t
-        })
+        let dict = VariantDict::new(Some(variant));
+
+        let t = TaskObject::new(&dict.lookup::<String>("name").ok()??);
+        t.set_checked(dict.lookup::<bool>("checked").ok()??);
+        *t.imp().id.borrow_mut() = dict.lookup::<IdType>("id").ok()??;
+        if let Some(notes) = dict.lookup::<String>("notes").ok()? {
+            t.set_notes(notes);
+        }
+        if let Some(due) = dict.lookup::<String>("due").ok()? {
+            t.set_due(Some(DueDate::parse(&due)?));
+        }
+        if let Some(priority) = dict.lookup::<String>("priority").ok()? {
+            t.set_priority(Priority::from(priority.as_str()));
+        }
+        if let Some(tags) = dict.lookup::<Vec<String>>("tags").ok()? {
+            t.set_tags(tags);
+        }
+        if let Some(recurrence) = dict.lookup::<String>("recurrence").ok()? {
+            t.set_recurrence(Some(serde_json::from_str::<Recurrence>(&recurrence).ok()?));
+        }
+        for subtask in dict
+            .lookup::<Vec<TaskObject>>("subtasks")
+            .ok()?
+            .unwrap_or_default()
+        {
+            t.subtasks().append(&subtask);
+        }
+        if let Some(derive_checked) = dict.lookup::<bool>("derive-checked").ok()? {
+            t.set_derive_checked(derive_checked);
+        }
+        t.set_created_at(match dict.lookup::<String>("created-at").ok()? {
+            Some(created_at) => Some(parse_timestamp(&created_at)?),
+            None => None,
+        });
+        t.set_modified_at(match dict.lookup::<String>("modified-at").ok()? {
+            Some(modified_at) => Some(parse_timestamp(&modified_at)?),
+            None => None,
+        });
+        t.set_completed_at(match dict.lookup::<String>("completed-at").ok()? {
+            Some(completed_at) => Some(parse_timestamp(&completed_at)?),
+            None => None,
+        });
+
+        Some(t)
     }
 }
 
 impl StaticVariantType for TaskObject {
     fn static_variant_type() -> Cow<'static, VariantTy> {
-        <(bool, String)>::static_variant_type()
+        VariantDict::static_variant_type()
     }
 }
 
 mod imp {
     use std::{
         cell::{Cell, RefCell},
-        sync::Mutex,
+        collections::HashMap,
     };
 
-    use gtk::glib::{self, Properties};
+    use gtk::{
+        gio::ListStore,
+        glib::{self, DateTime, Properties, SignalHandlerId},
+    };
 
     use adw::prelude::*;
     use glib::subclass::prelude::*;
+    use gtk_rs_test::list_store_ser::ListStoreSer;
     use serde::{Serialize, ser::SerializeStruct};
 
-    use super::IdType;
+    use super::{IdType, TaskObject, format_timestamp};
+    use crate::{due_date::DueDate, priority::Priority, recurrence::Recurrence};
 
     #[derive(Properties)]
     #[properties[wrapper_type=super::TaskObject]]
@@ -117,18 +468,113 @@ mod imp {
         checked: Cell<bool>,
         #[property(get, set)]
         name: RefCell<String>,
+        /// Free-form, possibly multi-line description
+        #[property(get, set)]
+        notes: RefCell<String>,
+        #[property(get, set, nullable)]
+        due: RefCell<Option<DueDate>>,
+        #[property(get, set, builder(Priority::default()))]
+        priority: Cell<Priority>,
+        /// Labels without the leading `#`, shared across collections
+        #[property(get, set)]
+        tags: RefCell<Vec<String>>,
+        #[property(get, set, nullable)]
+        recurrence: RefCell<Option<Recurrence>>,
+
+        /// ListStore storing the subtasks as TaskObject
+        #[property(get)]
+        subtasks: ListStore,
+        /// Whether `checked` follows the subtasks, being set once all of them are done
+        #[property(get, set)]
+        derive_checked: Cell<bool>,
+
+        #[property(get, set, nullable)]
+        created_at: RefCell<Option<DateTime>>,
+        /// Last time the name or the completion state changed
+        #[property(get, set, nullable)]
+        modified_at: RefCell<Option<DateTime>>,
+        /// When the task was checked, unset while it is unresolved
+        #[property(get, set, nullable)]
+        completed_at: RefCell<Option<DateTime>>,
+
+        pub(super) id: RefCell<IdType>,
+        /// Subtasks whose `checked` property is observed, with the handler doing so
+        watched_subtasks: RefCell<HashMap<TaskObject, SignalHandlerId>>,
+    }
+    impl TaskObjectImp {
+        /// Observe `checked` of the current subtasks only, as removed ones may live on
+        /// in the undo history
+        fn watch_subtasks(&self) {
+            let subtasks: Vec<TaskObject> = self
+                .subtasks
+                .iter::<TaskObject>()
+                .map(Result::unwrap)
+                .collect();
+            let mut watched = self.watched_subtasks.borrow_mut();
+            for (subtask, handler) in watched.extract_if(|subtask, _| !subtasks.contains(subtask)) {
+                subtask.disconnect(handler);
+            }
+
+            for subtask in subtasks {
+                if watched.contains_key(&subtask) {
+                    continue;
+                }
+                let task_object = self.downgrade();
+                let handler = subtask.connect_checked_notify(move |_| {
+                    if let Some(this) = task_object.upgrade() {
+                        this.update_derived_checked();
+                    }
+                });
+                watched.insert(subtask, handler);
+            }
+        }
+        fn update_derived_checked(&self) {
+            if !self.derive_checked.get() || self.subtasks.n_items() == 0 {
+                return;
+            }
 
-        pub(super) id: IdType,
+            let checked = self
+                .subtasks
+                .iter::<TaskObject>()
+                .map(Result::unwrap)
+                .all(|t| t.checked());
+            if checked != self.checked.get() {
+                self.obj().set_checked(checked);
+            }
+        }
     }
     impl Serialize for TaskObjectImp {
         fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
         where
             S: serde::Serializer,
         {
-            let mut stru = serializer.serialize_struct("TaskObject", 2)?;
+            let mut stru = serializer.serialize_struct("TaskObject", 13)?;
 
+            stru.serialize_field("id", &self.id)?;
             stru.serialize_field("checked", &self.checked)?;
             stru.serialize_field("name", &self.name)?;
+            stru.serialize_field("notes", &self.notes)?;
+            stru.serialize_field("due", &self.due)?;
+            stru.serialize_field("priority", &self.priority)?;
+            stru.serialize_field("tags", &self.tags)?;
+            stru.serialize_field("recurrence", &self.recurrence)?;
+            stru.serialize_field(
+                "subtasks",
+                &ListStoreSer::<TaskObject>::new(self.subtasks.clone()),
+            )?;
+            stru.serialize_field("derive_checked", &self.derive_checked)?;
+            stru.serialize_field(
+                "created_at",
+                &self.created_at.borrow().as_ref().map(format_timestamp),
+            )?;
+            stru.serialize_field(
+                "modified_at",
+                &self.modified_at.borrow().as_ref().map(format_timestamp),
+            )?;
+            stru.serialize_field(
+                "completed_at",
+                &self.completed_at.borrow().as_ref().map(format_timestamp),
+            )?;
 
             stru.end()
         }
@@ -136,17 +582,22 @@ mod imp {
 
     impl Default for TaskObjectImp {
         fn default() -> Self {
-            static ID: Mutex<IdType> = Mutex::new(0);
-            let mut id = ID.lock().unwrap();
-            let self_ = Self {
+            Self {
                 checked: Default::default(),
                 name: Default::default(),
-                id: *id,
-            };
-
-            *id += 1;
-
-            self_
+                notes: Default::default(),
+                due: Default::default(),
+                priority: Default::default(),
+                tags: Default::default(),
+                recurrence: Default::default(),
+                subtasks: ListStore::new::<TaskObject>(),
+                derive_checked: Default::default(),
+                created_at: RefCell::new(DateTime::now_utc().ok()),
+                modified_at: RefCell::new(DateTime::now_utc().ok()),
+                completed_at: Default::default(),
+                id: RefCell::new(glib::uuid_string_random().into()),
+                watched_subtasks: Default::default(),
+            }
         }
     }
 
@@ -157,5 +608,63 @@ mod imp {
     }
 
     #[glib::derived_properties]
-    impl ObjectImpl for TaskObjectImp {}
+    impl ObjectImpl for TaskObjectImp {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            // Derive completion from the subtasks when they change
+            let task_object = self.downgrade();
+            self.subtasks.connect_items_changed(move |_, _, _, _| {
+                if let Some(this) = task_object.upgrade() {
+                    this.watch_subtasks();
+                    this.update_derived_checked();
+                }
+            });
+            self.obj()
+                .connect_derive_checked_notify(|t| t.imp().update_derived_checked());
+
+            // Keep track of when the task was edited and completed
+            self.obj().connect_name_notify(|t| {
+                t.set_modified_at(DateTime::now_utc().ok());
+            });
+            self.obj().connect_checked_notify(|t| {
+                t.set_modified_at(DateTime::now_utc().ok());
+                if !t.checked() {
+                    t.set_completed_at(None::<DateTime>);
+                } else if t.completed_at().is_none() {
+                    t.set_completed_at(DateTime::now_utc().ok());
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn unknown_fields_are_skipped() {
+        let task_object: TaskObject = serde_json::from_value(json!({
+            "id": "a",
+            "checked": false,
+            "name": "Call Mom",
+            "color": "blue",
+            "reminder": { "minutes": 10 },
+            "subtasks": [{ "id": "b", "checked": true, "name": "Book flights", "pinned": true }],
+        }))
+        .unwrap();
+
+        assert_eq!(task_object.get_id(), "a");
+        assert_eq!(task_object.name(), "Call Mom");
+        let subtask = task_object
+            .subtasks()
+            .item(0)
+            .and_downcast::<TaskObject>()
+            .unwrap();
+        assert_eq!(subtask.name(), "Book flights");
+        assert!(subtask.checked());
+    }
 }